@@ -1,14 +1,20 @@
+use std::cmp;
 use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
 use rand::{XorShiftRng, SeedableRng};
 use player::Player;
 use config;
 use config::Solver;
 use zobrist::Zobrist;
 use field;
-use field::Field;
-use uct::UctRoot;
+use field::{Pos, Field};
+use uct::{UctRoot, UctMoveStats};
 use heuristic;
 use minimax;
+use minimax::MinimaxOptions;
+use hash_table::HashTable;
+use sgf::SgfGame;
+use rules::Rules;
 
 const BOT_STR: &'static str = "bot";
 
@@ -24,16 +30,64 @@ const MIN_MINIMAX_DEPTH: u32 = 0;
 
 const MAX_MINIMAX_DEPTH: u32 = 8;
 
+const MIN_MOVES_TO_GO: u32 = 10;
+
+const FREE_CELLS_PER_MOVE: u32 = 8;
+
+const OPENING_MOVES_COUNT: usize = 10;
+
+const MAX_TIME_EXTENSION: u32 = 3;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MoveEvaluation {
+  Uct {
+    win_rate: f64,
+    visits: usize
+  },
+  Minimax {
+    value: i32,
+    // Search depth the value is calculated with, none if the search was interrupted.
+    depth: Option<u32>
+  },
+  Heuristic(i32)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BotMove {
+  Put(u32, u32),
+  Pass,
+  Resign
+}
+
+pub struct ScoreEstimation {
+  pub score_lead: f64,
+  pub ownership: Vec<f64>
+}
+
 pub struct Bot {
   rng: XorShiftRng,
   zobrist: Arc<Zobrist>,
   field: Field,
-  uct: UctRoot
+  uct: UctRoot,
+  hash_table: HashTable,
+  rules: Rules,
+  mask: Vec<bool>,
+  // Depth reached by the last time limited minimax search.
+  search_depth: Option<u32>
 }
 
 impl Bot {
   pub fn new(width: u32, height: u32, seed: u64) -> Bot {
-    info!(target: BOT_STR, "Initialization with width {0}, height {1}, seed {2}.", width, height, seed);
+    Bot::with_rules(width, height, seed, Rules::default())
+  }
+
+  pub fn with_rules(width: u32, height: u32, seed: u64, rules: Rules) -> Bot {
+    Bot::with_mask(width, height, seed, rules, vec![false; (width * height) as usize])
+  }
+
+  // Masked cells are unplayable, the mask is given row by row.
+  pub fn with_mask(width: u32, height: u32, seed: u64, rules: Rules, mask: Vec<bool>) -> Bot {
+    info!(target: BOT_STR, "Initialization with width {0}, height {1}, seed {2}, rules {3:?} and {4} masked cells.", width, height, seed, rules, mask.iter().filter(|&&masked| masked).count());
     let length = field::length(width, height);
     let seed_array = [3, seed as u32, 7, (seed >> 32) as u32];
     let mut rng = XorShiftRng::from_seed(seed_array);
@@ -42,51 +96,267 @@ impl Bot {
     Bot {
       rng: rng,
       zobrist: zobrist,
-      field: Field::new(width, height, field_zobrist),
-      uct: UctRoot::new(length)
+      field: Field::with_mask(width, height, field_zobrist, rules, &mask),
+      uct: UctRoot::new(length),
+      hash_table: HashTable::new(config::minimax_hash_table_size()),
+      rules: rules,
+      mask: mask,
+      search_depth: None
     }
   }
 
-  pub fn best_move(&mut self, player: Player) -> Option<(u32, u32)> {
-    self.best_move_with_complexity(player, (MAX_COMPLEXITY - MIN_COMPLEXITY) / 2 + MIN_COMPLEXITY)
+  pub fn best_move(&mut self, player: Player, should_stop: &AtomicBool) -> Option<BotMove> {
+    self.best_move_with_complexity(player, (MAX_COMPLEXITY - MIN_COMPLEXITY) / 2 + MIN_COMPLEXITY, should_stop)
   }
 
-  pub fn best_move_with_time(&mut self, player: Player, time: u32) -> Option<(u32, u32)> {
-    match config::solver() {
+  pub fn best_move_with_time(&mut self, player: Player, time: u32, should_stop: &AtomicBool) -> Option<BotMove> {
+    self.search_depth = None;
+    if self.field.is_game_over() {
+      return None;
+    }
+    let pos = match config::solver() {
       Solver::Uct => {
-        self.uct.best_move_with_time(&self.field, player, &mut self.rng, time - config::time_gap())
+        self.uct.best_move_with_time(&self.field, player, &mut self.rng, time - config::time_gap(), should_stop)
           .or_else(|| { heuristic::heuristic(&self.field, player) })
-          .map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
       },
       Solver::Minimax => {
-        minimax::minimax_with_time(&mut self.field, player, &mut self.rng, time)
-          .or_else(|| { heuristic::heuristic(&self.field, player) })
-          .map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
+        // Minimax puts and undoes moves on the searched field, so it works on a copy to keep undone moves of the game for redo.
+        let (pos, depth) = minimax::minimax_with_time(&mut self.field.clone(), player, &self.hash_table, &mut self.rng, time, MinimaxOptions::from_config(), should_stop);
+        self.search_depth = Some(depth);
+        pos.or_else(|| { heuristic::heuristic(&self.field, player) })
       },
       Solver::Heuristic => {
-        heuristic::heuristic(&self.field, player).map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
+        heuristic::heuristic(&self.field, player)
       }
+    };
+    Some(self.choose_move(pos))
+  }
+
+  // Resigns when UCT gives the best move almost no chances and passes after the enemy's pass when the game is almost surely won.
+  // Also passes when there is no move to put. Other solvers give no win rate, so they never resign.
+  fn choose_move(&self, pos_option: Option<Pos>) -> BotMove {
+    let resign_threshold = config::uct_resign_threshold();
+    let win_rate = if config::solver() == Solver::Uct {
+      self.uct.stats(1).first().map(|move_stats| move_stats.win_rate)
+    } else {
+      None
+    };
+    match (pos_option, win_rate) {
+      (_, Some(win_rate)) if win_rate < resign_threshold => {
+        info!(target: BOT_STR, "Resigning with win rate {}.", win_rate);
+        BotMove::Resign
+      },
+      (_, Some(win_rate)) if win_rate > 1f64 - resign_threshold && self.field.is_last_move_pass() => {
+        info!(target: BOT_STR, "Passing after the enemy's pass with win rate {}.", win_rate);
+        BotMove::Pass
+      },
+      (Some(pos), _) => BotMove::Put(self.field.to_x(pos), self.field.to_y(pos)),
+      (None, _) => BotMove::Pass
     }
   }
 
-  pub fn best_move_with_complexity(&mut self, player: Player, complexity: u32) -> Option<(u32, u32)> {
-    match config::solver() {
+  fn free_cells_count(&self) -> u32 {
+    self.field.putting_allowed_count() as u32
+  }
+
+  // Returns minimal and maximal time for the next move. Maximal time never exceeds remaining time minus time gap.
+  fn time_for_move(&self, remaining_time: u32, increment: u32, moves_to_go: u32) -> (u32, u32) {
+    let available_time = remaining_time.saturating_sub(config::time_gap());
+    let expected_moves_to_go = if moves_to_go > 0 {
+      moves_to_go
+    } else {
+      cmp::max(self.free_cells_count() / FREE_CELLS_PER_MOVE, MIN_MOVES_TO_GO)
+    };
+    let mut min_time = available_time / expected_moves_to_go;
+    if increment < available_time {
+      min_time += increment * 3 / 4;
+    }
+    if self.field.moves_count() < OPENING_MOVES_COUNT {
+      min_time /= 2;
+    }
+    let max_time = if expected_moves_to_go > 1 {
+      cmp::min(min_time.saturating_mul(MAX_TIME_EXTENSION), available_time / 2)
+    } else {
+      available_time
+    };
+    (cmp::min(min_time, max_time), max_time)
+  }
+
+  pub fn best_move_with_clock(&mut self, player: Player, remaining_time: u32, increment: u32, moves_to_go: u32, should_stop: &AtomicBool) -> Option<BotMove> {
+    let (min_time, max_time) = self.time_for_move(remaining_time, increment, moves_to_go);
+    info!(target: BOT_STR, "Remaining time is {0}, increment is {1}, moves to go {2}. Time for move is {3} - {4}.", remaining_time, increment, moves_to_go, min_time, max_time);
+    self.search_depth = None;
+    if self.field.is_game_over() {
+      return None;
+    }
+    let pos = match config::solver() {
+      Solver::Uct => {
+        self.uct.best_move_with_stability(&self.field, player, &mut self.rng, min_time, max_time, should_stop)
+          .or_else(|| { heuristic::heuristic(&self.field, player) })
+      },
+      Solver::Minimax => {
+        let (pos, depth) = minimax::minimax_with_stability(&mut self.field.clone(), player, &self.hash_table, &mut self.rng, min_time, max_time, MinimaxOptions::from_config(), should_stop);
+        self.search_depth = Some(depth);
+        pos.or_else(|| { heuristic::heuristic(&self.field, player) })
+      },
+      Solver::Heuristic => {
+        heuristic::heuristic(&self.field, player)
+      }
+    };
+    Some(self.choose_move(pos))
+  }
+
+  // Depth reached by the last gen move with time or clock, none if it wasn't searched by minimax.
+  pub fn search_depth(&self) -> Option<u32> {
+    self.search_depth
+  }
+
+  pub fn best_move_with_complexity(&mut self, player: Player, complexity: u32, should_stop: &AtomicBool) -> Option<BotMove> {
+    if self.field.is_game_over() {
+      return None;
+    }
+    let pos = match config::solver() {
       Solver::Uct => {
         let iterations_count = (complexity - MIN_COMPLEXITY) as usize * (MAX_UCT_ITERATIONS - MIN_UCT_ITERATIONS) / (MAX_COMPLEXITY - MIN_COMPLEXITY) as usize + MIN_UCT_ITERATIONS;
-        self.uct.best_move_with_iterations_count(&self.field, player, &mut self.rng, iterations_count)
+        self.uct.best_move_with_iterations_count(&self.field, player, &mut self.rng, iterations_count, should_stop)
           .or_else(|| { heuristic::heuristic(&self.field, player) })
-          .map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
       },
       Solver::Minimax => {
         let depth = (complexity - MIN_COMPLEXITY) * (MAX_MINIMAX_DEPTH - MIN_MINIMAX_DEPTH) / (MAX_COMPLEXITY - MIN_COMPLEXITY) + MIN_MINIMAX_DEPTH;
-        minimax::minimax(&mut self.field, player, &mut self.rng, depth)
+        minimax::minimax(&mut self.field.clone(), player, &self.hash_table, &mut self.rng, depth, MinimaxOptions::from_config(), should_stop)
           .or_else(|| { heuristic::heuristic(&self.field, player) })
-          .map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
       },
       Solver::Heuristic => {
-        heuristic::heuristic(&self.field, player).map(|pos| (self.field.to_x(pos), self.field.to_y(pos)))
+        heuristic::heuristic(&self.field, player)
+      }
+    };
+    Some(self.choose_move(pos))
+  }
+
+  pub fn analyze<F: FnMut(&Field, &[UctMoveStats])>(&mut self, player: Player, interval: u32, moves_count: usize, should_stop: &AtomicBool, mut report: F) {
+    let field = &self.field;
+    self.uct.analyze(field, player, &mut self.rng, should_stop, interval, moves_count, |stats| report(field, stats));
+  }
+
+  // Ownership is given row by row, positive values mean the player's territory, negative values mean the enemy's one.
+  pub fn estimate_score(&mut self, player: Player, time: u32, should_stop: &AtomicBool) -> ScoreEstimation {
+    let estimation = self.uct.estimate(&self.field, player, &mut self.rng, time, should_stop);
+    let mut ownership = Vec::with_capacity((self.field.width() * self.field.height()) as usize);
+    for y in 0 .. self.field.height() {
+      for x in 0 .. self.field.width() {
+        ownership.push(estimation.ownership[self.field.to_pos(x, y)]);
+      }
+    }
+    ScoreEstimation {
+      score_lead: estimation.score_lead,
+      ownership: ownership
+    }
+  }
+
+  pub fn ponder(&mut self, should_stop: &AtomicBool) {
+    if config::solver() == Solver::Uct {
+      let player = self.field.cur_player();
+      self.uct.ponder(&self.field, player, &mut self.rng, should_stop);
+    }
+  }
+
+  pub fn set_moves(&mut self, width: u32, height: u32, moves: &[(u32, u32, Player)]) -> bool {
+    info!(target: BOT_STR, "Setting position with width {0}, height {1} and {2} moves.", width, height, moves.len());
+    let length = field::length(width, height);
+    let same_size = self.field.width() == width && self.field.height() == height;
+    let zobrist = if same_size {
+      self.zobrist.clone()
+    } else {
+      Arc::new(Zobrist::new(length * 2, &mut self.rng))
+    };
+    let mask = if same_size {
+      self.mask.clone()
+    } else {
+      vec![false; (width * height) as usize]
+    };
+    let mut field = Field::with_mask(width, height, zobrist.clone(), self.rules, &mask);
+    for &(x, y, player) in moves {
+      let pos = field.to_pos(x, y);
+      if x >= width || y >= height || !field.put_point(pos, player) {
+        warn!(target: BOT_STR, "Move ({0}, {1}) is not allowed.", x, y);
+        return false;
       }
     }
+    // Hash table entries keep moves as positions, which mean other cells on a field of another size.
+    if !same_size {
+      self.uct = UctRoot::new(length);
+      self.hash_table.clear();
+    }
+    self.zobrist = zobrist;
+    self.field = field;
+    self.mask = mask;
+    true
+  }
+
+  pub fn snapshot(&self) -> Vec<u8> {
+    self.field.snapshot()
+  }
+
+  // The snapshot must be taken by a bot with the same field size and seed, otherwise zobrist hashes will not match.
+  pub fn restore(&mut self, bytes: &[u8]) -> bool {
+    match Field::restore(bytes, self.zobrist.clone()) {
+      Ok(ref field) if field.width() != self.field.width() || field.height() != self.field.height() => {
+        warn!(target: BOT_STR, "Snapshot field size {0}x{1} differs from the current one.", field.width(), field.height());
+        false
+      },
+      Ok(field) => {
+        let mut mask = Vec::with_capacity((field.width() * field.height()) as usize);
+        for y in 0 .. field.height() {
+          for x in 0 .. field.width() {
+            mask.push(field.is_bad(field.to_pos(x, y)));
+          }
+        }
+        self.field = field;
+        self.mask = mask;
+        true
+      },
+      Err(error) => {
+        warn!(target: BOT_STR, "{}", error);
+        false
+      }
+    }
+  }
+
+  pub fn load_sgf(&mut self, game: &SgfGame, moves_count: Option<usize>) -> bool {
+    let moves_count = cmp::min(moves_count.unwrap_or(usize::max_value()), game.moves.len());
+    self.set_moves(game.width, game.height, &game.moves[.. moves_count])
+  }
+
+  pub fn best_moves(&mut self, player: Player, moves_count: usize, should_stop: &AtomicBool) -> Vec<(u32, u32, MoveEvaluation)> {
+    self.best_moves_with_complexity(player, (MAX_COMPLEXITY - MIN_COMPLEXITY) / 2 + MIN_COMPLEXITY, moves_count, should_stop)
+  }
+
+  pub fn best_moves_with_complexity(&mut self, player: Player, complexity: u32, moves_count: usize, should_stop: &AtomicBool) -> Vec<(u32, u32, MoveEvaluation)> {
+    if self.field.is_game_over() {
+      return Vec::new();
+    }
+    let moves = match config::solver() {
+      Solver::Uct => {
+        let iterations_count = (complexity - MIN_COMPLEXITY) as usize * (MAX_UCT_ITERATIONS - MIN_UCT_ITERATIONS) / (MAX_COMPLEXITY - MIN_COMPLEXITY) as usize + MIN_UCT_ITERATIONS;
+        self.uct.best_moves_with_iterations_count(&self.field, player, &mut self.rng, iterations_count, moves_count, should_stop).into_iter()
+          .map(|move_stats| (move_stats.pos, MoveEvaluation::Uct { win_rate: move_stats.win_rate, visits: move_stats.visits }))
+          .collect::<Vec<(Pos, MoveEvaluation)>>()
+      },
+      Solver::Minimax => {
+        let depth = (complexity - MIN_COMPLEXITY) * (MAX_MINIMAX_DEPTH - MIN_MINIMAX_DEPTH) / (MAX_COMPLEXITY - MIN_COMPLEXITY) + MIN_MINIMAX_DEPTH;
+        let (moves, completed_depth) = minimax::minimax_multi_pv(&mut self.field.clone(), player, &self.hash_table, &mut self.rng, depth, moves_count, MinimaxOptions::from_config(), should_stop);
+        moves.into_iter()
+          .map(|(pos, estimation)| (pos, MoveEvaluation::Minimax { value: estimation, depth: completed_depth }))
+          .collect()
+      },
+      Solver::Heuristic => Vec::new()
+    };
+    let moves = if moves.is_empty() {
+      heuristic::heuristic_moves(&self.field, player, moves_count).into_iter().map(|(pos, estimation)| (pos, MoveEvaluation::Heuristic(estimation))).collect()
+    } else {
+      moves
+    };
+    moves.into_iter().map(|(pos, evaluation)| (self.field.to_x(pos), self.field.to_y(pos), evaluation)).collect()
   }
 
   pub fn put_point(&mut self, x: u32, y: u32, player: Player) -> bool {
@@ -94,7 +364,37 @@ impl Bot {
     self.field.put_point(pos, player)
   }
 
+  // Allowed only when surroundings are declared manually.
+  pub fn put_point_without_surround(&mut self, x: u32, y: u32, player: Player) -> bool {
+    let pos = self.field.to_pos(x, y);
+    self.field.put_point_without_surround(pos, player)
+  }
+
+  pub fn pass(&mut self, player: Player) -> bool {
+    self.field.pass(player)
+  }
+
+  pub fn resign(&mut self, player: Player) -> bool {
+    self.field.resign(player)
+  }
+
+  pub fn ground(&mut self, player: Player) -> bool {
+    self.field.ground(player)
+  }
+
   pub fn undo(&mut self) -> bool {
-    self.field.undo()
+    self.field.undo_for_redo()
+  }
+
+  pub fn redo(&mut self) -> bool {
+    self.field.redo()
+  }
+
+  pub fn goto(&mut self, move_number: usize) -> bool {
+    self.field.goto(move_number)
+  }
+
+  pub fn field(&self) -> &Field {
+    &self.field
   }
 }
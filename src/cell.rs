@@ -28,6 +28,20 @@ impl Cell {
     }
   }
 
+  pub fn from_value(value: u8) -> Option<Cell> {
+    if value & !(PLAYER_BIT | PUT_BIT | CAPTURED_BIT | BOUND_BIT | EMPTY_BASE_BIT | BAD_BIT) == 0 {
+      Some(Cell {
+        value: value
+      })
+    } else {
+      None
+    }
+  }
+
+  pub fn to_value(self) -> u8 {
+    self.value
+  }
+
   pub fn get_player(self) -> Player {
     Player::from_bool(self.value & PLAYER_BIT != 0)
   }
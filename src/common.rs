@@ -1,6 +1,10 @@
+use std::{cmp, thread};
+use std::sync::atomic::{AtomicBool, Ordering};
 use player::Player;
 use field::{Pos, Field};
 
+const STOP_CHECK_INTERVAL: u32 = 10;
+
 pub fn is_last_move_stupid(field: &Field, pos: Pos, player: Player) -> bool {
   let delta_score = field.get_delta_score(player);
   delta_score < 0 || delta_score == 0 && {
@@ -28,3 +32,28 @@ pub fn is_penult_move_stuped(field: &Field) -> bool {
   let moves_count = field.moves_count();
   moves_count > 1 && field.is_captured(field.points_seq()[moves_count - 2])
 }
+
+// Sleeps for the given time in short intervals. Returns false as soon as should_stop is set.
+pub fn sleep_unless_stopped(time: u32, should_stop: &AtomicBool) -> bool {
+  let mut remaining_time = time;
+  while remaining_time > 0 {
+    if should_stop.load(Ordering::Relaxed) {
+      return false;
+    }
+    let sleep_time = cmp::min(remaining_time, STOP_CHECK_INTERVAL);
+    thread::sleep_ms(sleep_time);
+    remaining_time -= sleep_time;
+  }
+  !should_stop.load(Ordering::Relaxed)
+}
+
+// Sets time_should_stop after the time is over or should_stop is set. Returns earlier if time_should_stop is set by the search itself.
+pub fn wait_for_stop(time: u32, should_stop: &AtomicBool, time_should_stop: &AtomicBool) {
+  let mut remaining_time = time;
+  while remaining_time > 0 && !should_stop.load(Ordering::Relaxed) && !time_should_stop.load(Ordering::Relaxed) {
+    let sleep_time = cmp::min(remaining_time, STOP_CHECK_INTERVAL);
+    thread::sleep_ms(sleep_time);
+    remaining_time -= sleep_time;
+  }
+  time_should_stop.store(true, Ordering::Relaxed);
+}
@@ -8,6 +8,12 @@ use toml;
 
 const CONFIG_STR: &'static str = "config";
 
+const INVALID_VALUE_STR: &'static str = "Invalid value!";
+
+const UNKNOWN_OPTION_STR: &'static str = "Unknown option!";
+
+const AUTO_STR: &'static str = "auto";
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum UcbType {
   Winrate,
@@ -167,9 +173,60 @@ impl Decodable for Solver {
   }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MinimaxType {
+  // Full window principal variation search at the root.
+  NegaScout,
+  // Sequence of null window searches converging to the estimation.
+  MTDF
+}
+
+const NEGA_SCOUT_STR: &'static str = "NegaScout";
+
+const MTDF_STR: &'static str = "MTDF";
+
+impl MinimaxType {
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      MinimaxType::NegaScout => NEGA_SCOUT_STR,
+      MinimaxType::MTDF => MTDF_STR
+    }
+  }
+}
+
+impl FromStr for MinimaxType {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      NEGA_SCOUT_STR => Ok(MinimaxType::NegaScout),
+      MTDF_STR => Ok(MinimaxType::MTDF),
+      _ => Err("Invalid string!")
+    }
+  }
+}
+
+impl Display for MinimaxType {
+  fn fmt(&self, f: &mut Formatter) -> FmtResult {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+impl Encodable for MinimaxType {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    s.emit_str(self.as_str())
+  }
+}
+
+impl Decodable for MinimaxType {
+  fn decode<D: Decoder>(d: &mut D) -> Result<Self, D::Error> {
+    d.read_str().and_then(|s| MinimaxType::from_str(s.as_str()).map_err(|s| d.error(s)))
+  }
+}
+
 #[derive(Clone, RustcDecodable, RustcEncodable)]
-struct Config {
+pub struct Config {
   uct: UctConfig,
+  minimax: MinimaxConfig,
   bot: BotConfig
 }
 
@@ -185,14 +242,23 @@ struct UctConfig {
   komi_type: UctKomiType,
   red: f64,
   green: f64,
-  komi_min_iterations: usize
+  komi_min_iterations: usize,
+  resign_threshold: f64
+}
+
+#[derive(Clone, RustcDecodable, RustcEncodable)]
+struct MinimaxConfig {
+  minimax_type: MinimaxType,
+  hash_table_size: usize,
+  quiescence_depth: u32
 }
 
 #[derive(Clone, RustcDecodable, RustcEncodable)]
 struct BotConfig {
   threads_count: Option<usize>,
   time_gap: u32,
-  solver: Solver
+  solver: Solver,
+  ponder: bool
 }
 
 const DEFAULT_UCT_CONFIG: UctConfig = UctConfig {
@@ -206,17 +272,26 @@ const DEFAULT_UCT_CONFIG: UctConfig = UctConfig {
   komi_type: UctKomiType::Dynamic,
   red: 0.45,
   green: 0.5,
-  komi_min_iterations: 3000
+  komi_min_iterations: 3000,
+  resign_threshold: 0.02
+};
+
+const DEFAULT_MINIMAX_CONFIG: MinimaxConfig = MinimaxConfig {
+  minimax_type: MinimaxType::NegaScout,
+  hash_table_size: 1048576,
+  quiescence_depth: 4
 };
 
 const DEFAULT_BOT_CONFIG: BotConfig = BotConfig {
   threads_count: None,
   time_gap: 100,
-  solver: Solver::Uct
+  solver: Solver::Uct,
+  ponder: false
 };
 
 const DEFAULT_CONFIG: Config = Config {
   uct: DEFAULT_UCT_CONFIG,
+  minimax: DEFAULT_MINIMAX_CONFIG,
   bot: DEFAULT_BOT_CONFIG
 };
 
@@ -237,10 +312,37 @@ pub fn init() {
   info!(target: CONFIG_STR, "Default threads count is {}.", num_cpus);
 }
 
-pub fn read<T: Read>(input: &mut T) {
+// Overrides values of the base table with values of the given table, nested tables are merged recursively.
+fn merge_tables(base: &mut toml::Table, table: toml::Table) {
+  for (key, value) in table {
+    if let toml::Value::Table(sub_table) = value {
+      if let Some(&mut toml::Value::Table(ref mut base_sub_table)) = base.get_mut(&key) {
+        merge_tables(base_sub_table, sub_table);
+        continue;
+      }
+      base.insert(key, toml::Value::Table(sub_table));
+    } else {
+      base.insert(key, value);
+    }
+  }
+}
+
+// Options missing in the config file keep their default values, so config files written by older versions remain valid.
+pub fn parse_config<T: Read>(input: &mut T) -> Option<Config> {
   let mut string = String::new();
   input.read_to_string(&mut string).ok();
-  if let Some(config) = toml::decode_str::<Config>(string.as_str()) {
+  let mut config_table = match toml::encode(&DEFAULT_CONFIG) {
+    toml::Value::Table(table) => table,
+    _ => toml::Table::new()
+  };
+  toml::Parser::new(string.as_str()).parse().and_then(|table| {
+    merge_tables(&mut config_table, table);
+    toml::decode::<Config>(toml::Value::Table(config_table))
+  })
+}
+
+pub fn read<T: Read>(input: &mut T) {
+  if let Some(config) = parse_config(input) {
     unsafe {
       CONFIG = config
     }
@@ -255,6 +357,87 @@ pub fn write<T: Write>(output: &mut T) {
   info!(target: CONFIG_STR, "Config has been written.");
 }
 
+fn parse<T: FromStr>(value: &str) -> Result<T, &'static str> {
+  T::from_str(value).map_err(|_| INVALID_VALUE_STR)
+}
+
+fn parse_fraction(value: &str) -> Result<f64, &'static str> {
+  parse::<f64>(value).and_then(|fraction| if fraction >= 0f64 && fraction <= 1f64 { Ok(fraction) } else { Err(INVALID_VALUE_STR) })
+}
+
+fn parse_positive<T: FromStr + PartialOrd + Default>(value: &str) -> Result<T, &'static str> {
+  parse::<T>(value).and_then(|number| if number > T::default() { Ok(number) } else { Err(INVALID_VALUE_STR) })
+}
+
+pub fn set_option(name: &str, value: &str) -> Result<(), &'static str> {
+  let config = unsafe { &mut CONFIG };
+  let result = match name {
+    "radius" => parse(value).map(|radius| config.uct.radius = radius),
+    "ucb_type" => parse(value).map(|ucb_type| config.uct.ucb_type = ucb_type),
+    "final_ucb_type" => parse(value).map(|final_ucb_type| config.uct.final_ucb_type = final_ucb_type),
+    "draw_weight" => parse_fraction(value).map(|draw_weight| config.uct.draw_weight = draw_weight),
+    "uctk" => parse::<f64>(value).and_then(|uctk| if uctk >= 0f64 { Ok(uctk) } else { Err(INVALID_VALUE_STR) }).map(|uctk| config.uct.uctk = uctk),
+    "when_create_children" => parse(value).map(|when_create_children| config.uct.when_create_children = when_create_children),
+    "depth" => parse(value).map(|depth| config.uct.depth = depth),
+    "komi_type" => parse(value).map(|komi_type| config.uct.komi_type = komi_type),
+    "red" => parse_fraction(value).and_then(|red| if red < config.uct.green { Ok(red) } else { Err(INVALID_VALUE_STR) }).map(|red| config.uct.red = red),
+    "green" => parse_fraction(value).and_then(|green| if green > config.uct.red { Ok(green) } else { Err(INVALID_VALUE_STR) }).map(|green| config.uct.green = green),
+    "komi_min_iterations" => parse(value).map(|komi_min_iterations| config.uct.komi_min_iterations = komi_min_iterations),
+    "resign_threshold" => parse_fraction(value).map(|resign_threshold| config.uct.resign_threshold = resign_threshold),
+    "minimax_type" => parse(value).map(|minimax_type| config.minimax.minimax_type = minimax_type),
+    "hash_table_size" => parse_positive(value).map(|hash_table_size| config.minimax.hash_table_size = hash_table_size),
+    "quiescence_depth" => parse(value).map(|quiescence_depth| config.minimax.quiescence_depth = quiescence_depth),
+    "threads_count" => {
+      if value == AUTO_STR {
+        config.bot.threads_count = None;
+        Ok(())
+      } else {
+        parse_positive(value).map(|threads_count| config.bot.threads_count = Some(threads_count))
+      }
+    },
+    "time_gap" => parse(value).map(|time_gap| config.bot.time_gap = time_gap),
+    "solver" => parse(value).map(|solver| config.bot.solver = solver),
+    "ponder" => parse(value).map(|ponder| config.bot.ponder = ponder),
+    _ => Err(UNKNOWN_OPTION_STR)
+  };
+  match result {
+    Ok(()) => info!(target: CONFIG_STR, "Option {} has been set to {}.", name, value),
+    Err(error) => warn!(target: CONFIG_STR, "Option {} can't be set to {}: {}", name, value, error)
+  }
+  result
+}
+
+pub fn get_option(name: &str) -> Option<String> {
+  config().get_option(name)
+}
+
+impl Config {
+  pub fn get_option(&self, name: &str) -> Option<String> {
+    match name {
+      "radius" => Some(self.uct.radius.to_string()),
+      "ucb_type" => Some(self.uct.ucb_type.to_string()),
+      "final_ucb_type" => Some(self.uct.final_ucb_type.to_string()),
+      "draw_weight" => Some(self.uct.draw_weight.to_string()),
+      "uctk" => Some(self.uct.uctk.to_string()),
+      "when_create_children" => Some(self.uct.when_create_children.to_string()),
+      "depth" => Some(self.uct.depth.to_string()),
+      "komi_type" => Some(self.uct.komi_type.to_string()),
+      "red" => Some(self.uct.red.to_string()),
+      "green" => Some(self.uct.green.to_string()),
+      "komi_min_iterations" => Some(self.uct.komi_min_iterations.to_string()),
+      "resign_threshold" => Some(self.uct.resign_threshold.to_string()),
+      "minimax_type" => Some(self.minimax.minimax_type.to_string()),
+      "hash_table_size" => Some(self.minimax.hash_table_size.to_string()),
+      "quiescence_depth" => Some(self.minimax.quiescence_depth.to_string()),
+      "threads_count" => Some(self.bot.threads_count.map_or(AUTO_STR.to_owned(), |threads_count| threads_count.to_string())),
+      "time_gap" => Some(self.bot.time_gap.to_string()),
+      "solver" => Some(self.bot.solver.to_string()),
+      "ponder" => Some(self.bot.ponder.to_string()),
+      _ => None
+    }
+  }
+}
+
 #[inline]
 pub fn uct_radius() -> u32 {
   config().uct.radius
@@ -315,6 +498,26 @@ pub fn uct_komi_min_iterations() -> usize {
   config().uct.komi_min_iterations
 }
 
+#[inline]
+pub fn uct_resign_threshold() -> f64 {
+  config().uct.resign_threshold
+}
+
+#[inline]
+pub fn minimax_type() -> MinimaxType {
+  config().minimax.minimax_type
+}
+
+#[inline]
+pub fn minimax_hash_table_size() -> usize {
+  config().minimax.hash_table_size
+}
+
+#[inline]
+pub fn minimax_quiescence_depth() -> u32 {
+  config().minimax.quiescence_depth
+}
+
 #[inline]
 pub fn time_gap() -> u32 {
   config().bot.time_gap
@@ -324,3 +527,8 @@ pub fn time_gap() -> u32 {
 pub fn solver() -> Solver {
   config().bot.solver
 }
+
+#[inline]
+pub fn ponder() -> bool {
+  config().bot.ponder
+}
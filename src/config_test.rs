@@ -0,0 +1,12 @@
+use config;
+
+#[test]
+fn missing_options_keep_default_values() {
+  let mut input = "[bot]\nsolver = \"Uct\"\n\n[uct]\nradius = 3\n".as_bytes();
+  let config = config::parse_config(&mut input).unwrap();
+  assert_eq!(config.get_option("solver"), Some("Uct".to_owned()));
+  assert_eq!(config.get_option("radius"), Some("3".to_owned()));
+  assert_eq!(config.get_option("ponder"), Some("false".to_owned()));
+  assert_eq!(config.get_option("resign_threshold"), Some("0.02".to_owned()));
+  assert_eq!(config.get_option("quiescence_depth"), Some("4".to_owned()));
+}
@@ -1,12 +1,21 @@
-use std::{mem, iter};
+use std::{mem, iter, cmp};
 use std::collections::LinkedList;
 use std::sync::Arc;
 use player::Player;
 use cell::Cell;
 use zobrist::Zobrist;
+use rules::{Rules, SurroundRule, BaseInBaseRule, Topology};
+use snapshot;
+use snapshot::SnapshotReader;
 
 pub type Pos = usize;
 
+const SNAPSHOT_MAGIC: &'static [u8; 4] = b"OPFS";
+
+// Bumped on every change of the snapshot layout; snapshots of other versions
+// are rejected instead of being migrated.
+const SNAPSHOT_VERSION: u8 = 1;
+
 #[derive(Clone, PartialEq)]
 struct FieldChange {
   score_red: i32,
@@ -14,7 +23,138 @@ struct FieldChange {
   hash: u64,
   points_changes: Vec<(Pos, Cell)>,
   dsu_changes: Vec<(Pos, Pos)>,
-  dsu_size_change: Option<(Pos, u32)>
+  dsu_size_change: Option<(Pos, u32)>,
+  putting_allowed_count: usize,
+  captures: Vec<CaptureEvent>
+}
+
+// A capture made by a move. Captured enemy points increase the capturing player's score,
+// freed own points decrease the enemy's score.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CaptureEvent {
+  pub player: Player,
+  pub chain: Vec<Pos>,
+  pub captured: Vec<Pos>,
+  pub freed: Vec<Pos>
+}
+
+impl FieldChange {
+  fn write(&self, bytes: &mut Vec<u8>) {
+    snapshot::write_i32(bytes, self.score_red);
+    snapshot::write_i32(bytes, self.score_black);
+    snapshot::write_u64(bytes, self.hash);
+    snapshot::write_u32(bytes, self.putting_allowed_count as u32);
+    snapshot::write_u32(bytes, self.points_changes.len() as u32);
+    for &(pos, cell) in &self.points_changes {
+      snapshot::write_u32(bytes, pos as u32);
+      snapshot::write_u8(bytes, cell.to_value());
+    }
+    snapshot::write_u32(bytes, self.dsu_changes.len() as u32);
+    for &(pos, dsu_value) in &self.dsu_changes {
+      snapshot::write_u32(bytes, pos as u32);
+      snapshot::write_u32(bytes, dsu_value as u32);
+    }
+    if let Some((pos, dsu_size)) = self.dsu_size_change {
+      snapshot::write_u8(bytes, 1);
+      snapshot::write_u32(bytes, pos as u32);
+      snapshot::write_u32(bytes, dsu_size);
+    } else {
+      snapshot::write_u8(bytes, 0);
+    }
+    snapshot::write_u32(bytes, self.captures.len() as u32);
+    for capture in &self.captures {
+      snapshot::write_u8(bytes, capture.player.to_bool() as u8);
+      for positions in &[&capture.chain, &capture.captured, &capture.freed] {
+        snapshot::write_u32(bytes, positions.len() as u32);
+        for &pos in positions.iter() {
+          snapshot::write_u32(bytes, pos as u32);
+        }
+      }
+    }
+  }
+
+  fn read(reader: &mut SnapshotReader, length: Pos) -> Result<FieldChange, &'static str> {
+    let score_red = try!(reader.read_i32());
+    let score_black = try!(reader.read_i32());
+    let hash = try!(reader.read_u64());
+    let putting_allowed_count = try!(read_pos(reader, length + 1));
+    let points_changes_count = try!(reader.read_u32());
+    let mut points_changes = Vec::new();
+    for _ in 0 .. points_changes_count {
+      let pos = try!(read_pos(reader, length));
+      let cell = try!(read_cell(reader));
+      points_changes.push((pos, cell));
+    }
+    let dsu_changes_count = try!(reader.read_u32());
+    let mut dsu_changes = Vec::new();
+    for _ in 0 .. dsu_changes_count {
+      let pos = try!(read_pos(reader, length));
+      let dsu_value = try!(read_pos(reader, length));
+      dsu_changes.push((pos, dsu_value));
+    }
+    let dsu_size_change = if try!(reader.read_bool()) {
+      let pos = try!(read_pos(reader, length));
+      Some((pos, try!(reader.read_u32())))
+    } else {
+      None
+    };
+    let captures_count = try!(reader.read_u32());
+    let mut captures = Vec::new();
+    for _ in 0 .. captures_count {
+      let player = Player::from_bool(try!(reader.read_bool()));
+      let chain = try!(read_positions(reader, length));
+      let captured = try!(read_positions(reader, length));
+      let freed = try!(read_positions(reader, length));
+      captures.push(CaptureEvent {
+        player: player,
+        chain: chain,
+        captured: captured,
+        freed: freed
+      });
+    }
+    Ok(FieldChange {
+      score_red: score_red,
+      score_black: score_black,
+      hash: hash,
+      points_changes: points_changes,
+      dsu_changes: dsu_changes,
+      dsu_size_change: dsu_size_change,
+      putting_allowed_count: putting_allowed_count,
+      captures: captures
+    })
+  }
+}
+
+// Reads a position and checks that it is less than the bound.
+fn read_pos(reader: &mut SnapshotReader, bound: Pos) -> Result<Pos, &'static str> {
+  let pos = try!(reader.read_u32()) as Pos;
+  if pos < bound {
+    Ok(pos)
+  } else {
+    Err("Invalid position in snapshot!")
+  }
+}
+
+fn read_positions(reader: &mut SnapshotReader, bound: Pos) -> Result<Vec<Pos>, &'static str> {
+  let count = try!(reader.read_u32());
+  let mut positions = Vec::new();
+  for _ in 0 .. count {
+    positions.push(try!(read_pos(reader, bound)));
+  }
+  Ok(positions)
+}
+
+fn read_player_option(reader: &mut SnapshotReader) -> Result<Option<Player>, &'static str> {
+  match try!(reader.read_u8()) {
+    0 => Ok(None),
+    1 => Ok(Some(Player::Red)),
+    2 => Ok(Some(Player::Black)),
+    _ => Err("Invalid snapshot!")
+  }
+}
+
+fn read_cell(reader: &mut SnapshotReader) -> Result<Cell, &'static str> {
+  Cell::from_value(try!(reader.read_u8())).ok_or("Invalid cell in snapshot!")
 }
 
 #[derive(Clone, Copy, PartialEq)]
@@ -38,7 +178,38 @@ pub struct Field {
   dsu_size: Vec<u32>,
   changes: Vec<FieldChange>,
   zobrist: Arc<Zobrist>,
-  hash: u64
+  hash: u64,
+  rules: Rules,
+  grounded: Option<Player>,
+  resigned: Option<Player>,
+  putting_allowed_count: usize,
+  // Undone moves with their changes, the last one is the next move to redo.
+  redo_changes: Vec<(Pos, FieldChange)>,
+  // Undone grounding or resignation, it's redone after all undone moves.
+  redo_grounded: Option<Player>,
+  redo_resigned: Option<Player>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GameResult {
+  // None means a draw.
+  pub winner: Option<Player>,
+  // Final score of the winner, never negative: zero for a draw and for a resignation when the winner
+  // isn't ahead on the board.
+  pub score: i32,
+  // Whether the game was won by resignation of the other player.
+  pub resignation: bool
+}
+
+// Passes are kept in the moves sequence as positions of the top left padding cells: 0 for red and 1 for black.
+#[inline]
+pub fn pass_pos(player: Player) -> Pos {
+  player.to_bool() as Pos
+}
+
+#[inline]
+pub fn is_pass(pos: Pos) -> bool {
+  pos < 2
 }
 
 #[inline]
@@ -122,13 +293,28 @@ pub fn is_near(width: u32, pos1: Pos, pos2: Pos) -> bool {
   se(width, pos1) == pos2
 }
 
-fn get_intersection_state(width: u32, pos: Pos, next_pos: Pos) -> IntersectionState {
-  let pos_x = to_x(width, pos);
-  let pos_y = to_y(width, pos);
-  let next_pos_x = to_x(width, next_pos);
-  let next_pos_y = to_y(width, next_pos);
+// Maps a position in the padding border to the cell on the opposite side of a toroidal field.
+#[inline]
+pub fn wrap(width: u32, height: u32, pos: Pos) -> Pos {
+  let row_length = width as Pos + 2;
+  let mut x = pos % row_length;
+  let mut y = pos / row_length;
+  if x == 0 {
+    x = width as Pos;
+  } else if x == width as Pos + 1 {
+    x = 1;
+  }
+  if y == 0 {
+    y = height as Pos;
+  } else if y == height as Pos + 1 {
+    y = 1;
+  }
+  y * row_length + x
+}
+
+fn get_intersection_state((pos_x, pos_y): (i32, i32), (next_pos_x, next_pos_y): (i32, i32)) -> IntersectionState {
   if next_pos_x <= pos_x {
-    match next_pos_y as i32 - pos_y as i32 {
+    match next_pos_y - pos_y {
       1  => IntersectionState::Up,
       0  => IntersectionState::Target,
       -1 => IntersectionState::Down,
@@ -140,10 +326,15 @@ fn get_intersection_state(width: u32, pos: Pos, next_pos: Pos) -> IntersectionSt
 }
 
 pub fn is_point_inside_ring(width: u32, pos: Pos, ring: &LinkedList<Pos>) -> bool {
+  let ring_coordinates = ring.iter().map(|&next_pos| (to_x(width, next_pos) as i32, to_y(width, next_pos) as i32)).collect::<Vec<(i32, i32)>>();
+  is_point_inside_polygon((to_x(width, pos) as i32, to_y(width, pos) as i32), &ring_coordinates)
+}
+
+fn is_point_inside_polygon(point: (i32, i32), ring: &[(i32, i32)]) -> bool {
   let mut intersections = 0u32;
   let mut state = IntersectionState::None;
-  for &next_pos in ring {
-    match get_intersection_state(width, pos, next_pos) {
+  for &next_point in ring {
+    match get_intersection_state(point, next_point) {
       IntersectionState::None => {
         state = IntersectionState::None;
       },
@@ -164,9 +355,9 @@ pub fn is_point_inside_ring(width: u32, pos: Pos, ring: &LinkedList<Pos>) -> boo
   }
   if state == IntersectionState::Up || state == IntersectionState::Down {
     let mut iter = ring.iter();
-    let mut begin_state = get_intersection_state(width, pos, *iter.next().unwrap());
+    let mut begin_state = get_intersection_state(point, *iter.next().unwrap());
     while begin_state == IntersectionState::Target {
-      begin_state = get_intersection_state(width, pos, *iter.next().unwrap());
+      begin_state = get_intersection_state(point, *iter.next().unwrap());
     }
     if state == IntersectionState::Up && begin_state == IntersectionState::Down || state == IntersectionState::Down && begin_state == IntersectionState::Up {
       intersections += 1;
@@ -180,17 +371,18 @@ pub fn square(width: u32, pos1: Pos, pos2: Pos) -> i32 {
   (to_x(width, pos1) * to_y(width, pos2)) as i32 - (to_y(width, pos1) * to_x(width, pos2)) as i32
 }
 
-pub fn wave<F: FnMut(Pos) -> bool>(width: u32, start_pos: Pos, mut cond: F) {
+pub fn wave<F: FnMut(Pos) -> bool>(width: u32, height: u32, topology: Topology, start_pos: Pos, mut cond: F) {
   if !cond(start_pos) {
     return;
   }
+  let wrap_pos = |pos: Pos| if topology == Topology::Torus { wrap(width, height, pos) } else { pos };
   let mut queue = LinkedList::new();
   queue.push_back(start_pos);
   while let Some(pos) = queue.pop_front() {
-    let n_pos = n(width, pos);
-    let s_pos = s(width, pos);
-    let w_pos = w(pos);
-    let e_pos = e(pos);
+    let n_pos = wrap_pos(n(width, pos));
+    let s_pos = wrap_pos(s(width, pos));
+    let w_pos = wrap_pos(w(pos));
+    let e_pos = wrap_pos(e(pos));
     if cond(n_pos) {
       queue.push_back(n_pos);
     }
@@ -232,44 +424,60 @@ impl Field {
     to_y(self.width, pos)
   }
 
+  #[inline]
+  pub fn topology(&self) -> Topology {
+    self.rules.topology
+  }
+
+  // On a toroidal field maps positions of the padding border to the opposite side of the field.
+  // A plane field is checked first, so that neighbours on it cost a single predictable comparison.
+  #[inline]
+  fn wrap(&self, pos: Pos) -> Pos {
+    if self.rules.topology == Topology::Plane {
+      pos
+    } else {
+      wrap(self.width, self.height, pos)
+    }
+  }
+
   #[inline]
   pub fn n(&self, pos: Pos) -> Pos {
-    n(self.width, pos)
+    self.wrap(n(self.width, pos))
   }
 
   #[inline]
   pub fn s(&self, pos: Pos) -> Pos {
-    s(self.width, pos)
+    self.wrap(s(self.width, pos))
   }
 
   #[inline]
   pub fn w(&self, pos: Pos) -> Pos {
-    w(pos)
+    self.wrap(w(pos))
   }
 
   #[inline]
   pub fn e(&self, pos: Pos) -> Pos {
-    e(pos)
+    self.wrap(e(pos))
   }
 
   #[inline]
   pub fn nw(&self, pos: Pos) -> Pos {
-    nw(self.width, pos)
+    self.wrap(nw(self.width, pos))
   }
 
   #[inline]
   pub fn ne(&self, pos: Pos) -> Pos {
-    ne(self.width, pos)
+    self.wrap(ne(self.width, pos))
   }
 
   #[inline]
   pub fn sw(&self, pos: Pos) -> Pos {
-    sw(self.width, pos)
+    self.wrap(sw(self.width, pos))
   }
 
   #[inline]
   pub fn se(&self, pos: Pos) -> Pos {
-    se(self.width, pos)
+    self.wrap(se(self.width, pos))
   }
 
   #[inline]
@@ -284,7 +492,38 @@ impl Field {
 
   #[inline]
   pub fn is_near(&self, pos1: Pos, pos2: Pos) -> bool {
-    is_near(self.width, pos1, pos2)
+    if self.rules.topology == Topology::Plane {
+      is_near(self.width, pos1, pos2)
+    } else {
+      self.n(pos1) == pos2 || self.s(pos1) == pos2 || self.w(pos1) == pos2 || self.e(pos1) == pos2 ||
+      self.nw(pos1) == pos2 || self.ne(pos1) == pos2 || self.sw(pos1) == pos2 || self.se(pos1) == pos2
+    }
+  }
+
+  // Returns the shortest coordinate shift from pos1 to pos2, taking wrapping of a toroidal field into account.
+  pub fn delta(&self, pos1: Pos, pos2: Pos) -> (i32, i32) {
+    let mut dx = self.to_x(pos2) as i32 - self.to_x(pos1) as i32;
+    let mut dy = self.to_y(pos2) as i32 - self.to_y(pos1) as i32;
+    if self.rules.topology == Topology::Torus {
+      let width = self.width as i32;
+      let height = self.height as i32;
+      if dx > width / 2 {
+        dx -= width;
+      } else if dx < -width / 2 {
+        dx += width;
+      }
+      if dy > height / 2 {
+        dy -= height;
+      } else if dy < -height / 2 {
+        dy += height;
+      }
+    }
+    (dx, dy)
+  }
+
+  pub fn manhattan(&self, pos1: Pos, pos2: Pos) -> u32 {
+    let (dx, dy) = self.delta(pos1, pos2);
+    (dx.abs() + dy.abs()) as u32
   }
 
   #[inline]
@@ -364,12 +603,18 @@ impl Field {
 
   #[inline]
   pub fn set_bad(&mut self, pos: Pos) {
+    if self.is_putting_allowed(pos) {
+      self.putting_allowed_count -= 1;
+    }
     self.points[pos].set_bad()
   }
 
   #[inline]
   pub fn clear_bad(&mut self, pos: Pos) {
-    self.points[pos].clear_bad()
+    self.points[pos].clear_bad();
+    if self.is_putting_allowed(pos) {
+      self.putting_allowed_count += 1;
+    }
   }
 
   #[inline]
@@ -480,7 +725,27 @@ impl Field {
     result
   }
 
-  pub fn new(width: u32, height: u32, zobrist: Arc<Zobrist>) -> Field {
+  // Returns the representative of the group containing the point. Groups are joined when a point connecting them is put
+  // and are never split, so captured points stay in their groups.
+  pub fn group_of(&self, pos: Pos) -> Option<Pos> {
+    if !self.is_put(pos) {
+      return None;
+    }
+    let mut result = pos;
+    while self.dsu[result] != result {
+      result = self.dsu[result];
+    }
+    Some(result)
+  }
+
+  pub fn group_members(&self, pos: Pos) -> Vec<Pos> {
+    match self.group_of(pos) {
+      Some(group) => (self.min_pos() .. self.max_pos() + 1).filter(|&member_pos| self.group_of(member_pos) == Some(group)).collect(),
+      None => Vec::new()
+    }
+  }
+
+  pub fn new(width: u32, height: u32, zobrist: Arc<Zobrist>, rules: Rules) -> Field {
     let length = length(width, height);
     let mut field = Field {
       width: width,
@@ -494,7 +759,14 @@ impl Field {
       dsu_size: iter::repeat(1).take(length).collect(),
       changes: Vec::with_capacity(length),
       zobrist: zobrist,
-      hash: 0
+      hash: 0,
+      rules: rules,
+      grounded: None,
+      resigned: None,
+      putting_allowed_count: length,
+      redo_changes: Vec::new(),
+      redo_grounded: None,
+      redo_resigned: None
     };
     let max_pos = field.max_pos();
     for x in 0 .. width as Pos + 2 {
@@ -508,6 +780,20 @@ impl Field {
     field
   }
 
+  // Creates a field of an arbitrary shape. The mask is given row by row, masked cells are unplayable and act as walls.
+  pub fn with_mask(width: u32, height: u32, zobrist: Arc<Zobrist>, rules: Rules, mask: &[bool]) -> Field {
+    let mut field = Field::new(width, height, zobrist, rules);
+    for y in 0 .. height {
+      for x in 0 .. width {
+        if mask[(y * width + x) as usize] {
+          let pos = field.to_pos(x, y);
+          field.set_bad(pos);
+        }
+      }
+    }
+    field
+  }
+
   #[inline]
   fn save_pos_value(&mut self, pos: Pos) {
     self.changes.last_mut().unwrap().points_changes.push((pos, self.points[pos]));
@@ -556,11 +842,6 @@ impl Field {
     inp_points
   }
 
-  #[inline]
-  fn square(&self, pos1: Pos, pos2: Pos) -> i32 {
-    square(self.width, pos1, pos2)
-  }
-
   //  * . .   x . *   . x x   . . .
   //  . o .   x o .   . o .   . o x
   //  x x .   . . .   . . *   * . x
@@ -568,18 +849,31 @@ impl Field {
   //  x - pos
   //  * - result
   fn get_first_next_pos(&self, center_pos: Pos, pos: Pos) -> Pos {
-    if pos < center_pos {
-      if pos == self.nw(center_pos) || pos == self.w(center_pos) {
-        self.ne(center_pos)
+    // On a plane field positions before the center are nw, n, ne and w, so one comparison halves the checks.
+    // Wrapped positions on a torus break this order.
+    if self.rules.topology == Topology::Plane {
+      return if pos < center_pos {
+        if pos == self.nw(center_pos) || pos == self.w(center_pos) {
+          self.ne(center_pos)
+        } else {
+          self.se(center_pos)
+        }
       } else {
-        self.se(center_pos)
-      }
+        if pos == self.e(center_pos) || pos == self.se(center_pos) {
+          self.sw(center_pos)
+        } else {
+          self.nw(center_pos)
+        }
+      };
+    }
+    if pos == self.nw(center_pos) || pos == self.w(center_pos) {
+      self.ne(center_pos)
+    } else if pos == self.n(center_pos) || pos == self.ne(center_pos) {
+      self.se(center_pos)
+    } else if pos == self.e(center_pos) || pos == self.se(center_pos) {
+      self.sw(center_pos)
     } else {
-      if pos == self.e(center_pos) || pos == self.se(center_pos) {
-        self.sw(center_pos)
-      } else {
-        self.nw(center_pos)
-      }
+      self.nw(center_pos)
     }
   }
 
@@ -590,26 +884,45 @@ impl Field {
   //  x - pos
   //  * - result
   fn get_next_pos(&self, center_pos: Pos, pos: Pos) -> Pos {
-    if pos < center_pos {
-      if pos == self.nw(center_pos) {
-        self.n(center_pos)
-      } else if pos == self.n(center_pos) {
-        self.ne(center_pos)
-      } else if pos == self.ne(center_pos) {
-        self.e(center_pos)
+    if self.rules.topology == Topology::Plane {
+      return if pos < center_pos {
+        if pos == self.nw(center_pos) {
+          self.n(center_pos)
+        } else if pos == self.n(center_pos) {
+          self.ne(center_pos)
+        } else if pos == self.ne(center_pos) {
+          self.e(center_pos)
+        } else {
+          self.nw(center_pos)
+        }
       } else {
-        self.nw(center_pos)
-      }
+        if pos == self.e(center_pos) {
+          self.se(center_pos)
+        } else if pos == self.se(center_pos) {
+          self.s(center_pos)
+        } else if pos == self.s(center_pos) {
+          self.sw(center_pos)
+        } else {
+          self.w(center_pos)
+        }
+      };
+    }
+    if pos == self.nw(center_pos) {
+      self.n(center_pos)
+    } else if pos == self.n(center_pos) {
+      self.ne(center_pos)
+    } else if pos == self.ne(center_pos) {
+      self.e(center_pos)
+    } else if pos == self.e(center_pos) {
+      self.se(center_pos)
+    } else if pos == self.se(center_pos) {
+      self.s(center_pos)
+    } else if pos == self.s(center_pos) {
+      self.sw(center_pos)
+    } else if pos == self.sw(center_pos) {
+      self.w(center_pos)
     } else {
-      if pos == self.e(center_pos) {
-        self.se(center_pos)
-      } else if pos == self.se(center_pos) {
-        self.s(center_pos)
-      } else if pos == self.s(center_pos) {
-        self.sw(center_pos)
-      } else {
-        self.w(center_pos)
-      }
+      self.nw(center_pos)
     }
   }
 
@@ -618,7 +931,12 @@ impl Field {
     chain.push_back(start_pos);
     let mut pos = direction_pos;
     let mut center_pos = start_pos;
-    let mut base_square = self.square(center_pos, pos);
+    // Coordinates are tracked without wrapping, so on a toroidal field a chain that winds around the field does not return to the start coordinates.
+    let start_coordinates = (self.to_x(start_pos) as i32, self.to_y(start_pos) as i32);
+    let (dx, dy) = self.delta(center_pos, pos);
+    let mut center_coordinates = start_coordinates;
+    let mut coordinates = (start_coordinates.0 + dx, start_coordinates.1 + dy);
+    let mut base_square = center_coordinates.0 * coordinates.1 - center_coordinates.1 * coordinates.0;
     loop {
       if self.is_tagged(pos) {
         while *chain.back().unwrap() != pos {
@@ -630,26 +948,60 @@ impl Field {
         chain.push_back(pos);
       }
       mem::swap(&mut pos, &mut center_pos);
+      center_coordinates = coordinates;
       pos = self.get_first_next_pos(center_pos, pos);
       while !self.is_live_players_point(pos, player) {
         pos = self.get_next_pos(center_pos, pos);
       }
-      base_square += self.square(center_pos, pos);
+      let (dx, dy) = self.delta(center_pos, pos);
+      coordinates = (center_coordinates.0 + dx, center_coordinates.1 + dy);
+      base_square += center_coordinates.0 * coordinates.1 - center_coordinates.1 * coordinates.0;
       if pos == start_pos { break }
     }
     for &pos in &chain {
       self.clear_tag(pos);
     }
-    if base_square < 0 && chain.len() > 2 {
+    if base_square < 0 && chain.len() > 2 && coordinates == start_coordinates {
       Some(chain)
     } else {
       None
     }
   }
 
-  #[inline]
   pub fn is_point_inside_ring(&self, pos: Pos, ring: &LinkedList<Pos>) -> bool {
-    is_point_inside_ring(self.width, pos, ring)
+    if self.rules.topology != Topology::Torus {
+      return is_point_inside_ring(self.width, pos, ring);
+    }
+    // On a toroidal field the ring is unwrapped into a polygon, and all copies of the point that may fall into it are checked.
+    let mut ring_coordinates = Vec::with_capacity(ring.len());
+    let mut iter = ring.iter();
+    let mut prev_pos = *iter.next().unwrap();
+    let mut coordinates = (self.to_x(prev_pos) as i32, self.to_y(prev_pos) as i32);
+    ring_coordinates.push(coordinates);
+    for &next_pos in iter {
+      let (dx, dy) = self.delta(prev_pos, next_pos);
+      coordinates = (coordinates.0 + dx, coordinates.1 + dy);
+      ring_coordinates.push(coordinates);
+      prev_pos = next_pos;
+    }
+    let min_x = ring_coordinates.iter().map(|&(x, _)| x).min().unwrap();
+    let max_x = ring_coordinates.iter().map(|&(x, _)| x).max().unwrap();
+    let min_y = ring_coordinates.iter().map(|&(_, y)| y).min().unwrap();
+    let max_y = ring_coordinates.iter().map(|&(_, y)| y).max().unwrap();
+    let width = self.width as i32;
+    let height = self.height as i32;
+    let mut x = min_x + ((self.to_x(pos) as i32 - min_x) % width + width) % width;
+    while x <= max_x {
+      let mut y = min_y + ((self.to_y(pos) as i32 - min_y) % height + height) % height;
+      while y <= max_y {
+        if is_point_inside_polygon((x, y), &ring_coordinates) {
+          return true;
+        }
+        y += height;
+      }
+      x += width;
+    }
+    false
   }
 
   #[inline]
@@ -665,18 +1017,28 @@ impl Field {
     let mut captured_count = 0i32;
     let mut freed_count = 0i32;
     let mut captured_points = LinkedList::new();
+    let mut bad_points = LinkedList::new();
+    let mut captured_enemy_points = Vec::new();
+    let mut freed_points = Vec::new();
     for &pos in chain {
       self.set_tag(pos);
     }
-    wave(self.width, inside_pos, |pos| {
+    wave(self.width, self.height, self.rules.topology, inside_pos, |pos| {
       if !self.is_tagged(pos) && !self.is_bound_player(pos, player) {
         self.set_tag(pos);
-        captured_points.push_back(pos);
+        // Unplayable cells inside a surrounding are passed through, but they are neither captured nor become empty bases.
+        if self.is_bad(pos) {
+          bad_points.push_back(pos);
+        } else {
+          captured_points.push_back(pos);
+        }
         if self.is_put(pos) {
           if self.get_player(pos) != player {
             captured_count += 1;
-          } else if self.is_captured(pos) {
+            captured_enemy_points.push(pos);
+          } else if self.is_captured(pos) && self.rules.base_in_base == BaseInBaseRule::Free {
             freed_count += 1;
+            freed_points.push(pos);
           }
         }
         true
@@ -695,8 +1057,10 @@ impl Field {
           self.score_red -= freed_count;
         }
       }
-      for &pos in chain.iter() {
+      for &pos in chain.iter().chain(bad_points.iter()) {
         self.clear_tag(pos);
+      }
+      for &pos in chain.iter() {
         self.save_pos_value(pos);
         self.set_bound(pos);
       }
@@ -706,6 +1070,7 @@ impl Field {
         if !self.is_put(pos) {
           if !self.is_captured(pos) {
             self.set_captured(pos);
+            self.putting_allowed_count -= 1;
           } else {
             self.update_hash(pos, player.next());
           }
@@ -717,21 +1082,28 @@ impl Field {
             self.set_captured(pos);
             self.update_hash(pos, player.next());
             self.update_hash(pos, player);
-          } else if self.is_captured(pos) {
+          } else if self.is_captured(pos) && self.rules.base_in_base == BaseInBaseRule::Free {
             self.clear_captured(pos);
             self.update_hash(pos, player.next());
             self.update_hash(pos, player);
           }
         }
       }
+      self.changes.last_mut().unwrap().captures.push(CaptureEvent {
+        player: player,
+        chain: chain.iter().cloned().collect(),
+        captured: captured_enemy_points,
+        freed: freed_points
+      });
       true
     } else {
-      for &pos in chain.iter() {
+      for &pos in chain.iter().chain(bad_points.iter()) {
         self.clear_tag(pos);
       }
+      let empty_base_capture = self.rules.empty_base_capture;
       for &pos in &captured_points {
         self.clear_tag(pos);
-        if !self.is_put(pos) {
+        if !self.is_put(pos) && empty_base_capture {
           self.save_pos_value(pos);
           self.set_empty_base_player(pos, player);
         }
@@ -774,7 +1146,7 @@ impl Field {
     parent
   }
 
-  fn find_captures(&mut self, pos: Pos, player: Player) -> bool {
+  fn find_captures(&mut self, pos: Pos, player: Player, surround: bool) -> bool {
     let input_points = self.get_input_points(pos, player);
     let input_points_count = input_points.len();
     if input_points_count > 1 {
@@ -792,7 +1164,7 @@ impl Field {
           }
         }
         let group_points_count = group.len() as u32;
-        if group_points_count > 1 {
+        if surround && group_points_count > 1 {
           let mut chains_count = 0u32;
           for &(chain_pos, captured_pos) in &group {
             if let Some(chain) = self.build_chain(pos, player, chain_pos) {
@@ -830,7 +1202,7 @@ impl Field {
 
   #[inline]
   fn remove_empty_base(&mut self, start_pos: Pos) {
-    wave(self.width, start_pos, |pos| {
+    wave(self.width, self.height, self.rules.topology, start_pos, |pos| {
       if self.is_empty_base(pos) {
         self.save_pos_value(pos);
         self.clear_empty_base(pos);
@@ -841,26 +1213,31 @@ impl Field {
     })
   }
 
+  // Puts a point, all surroundings made by it are declared.
   pub fn put_point(&mut self, pos: Pos, player: Player) -> bool {
-    if self.is_putting_allowed(pos) {
-      let change = FieldChange {
-        score_red: self.score_red,
-        score_black: self.score_black,
-        hash: self.hash,
-        points_changes: Vec::new(),
-        dsu_changes: Vec::new(),
-        dsu_size_change: None
-      };
+    self.put_point_generic(pos, player, true)
+  }
+
+  // Puts a point without declaring surroundings. Allowed only when surroundings are declared manually.
+  pub fn put_point_without_surround(&mut self, pos: Pos, player: Player) -> bool {
+    self.rules.surround == SurroundRule::Manual && self.put_point_generic(pos, player, false)
+  }
+
+  fn put_point_generic(&mut self, pos: Pos, player: Player, surround: bool) -> bool {
+    if !self.is_game_over() && self.is_putting_allowed(pos) {
+      self.clear_redo();
+      let change = self.new_change();
       self.changes.push(change);
       self.save_pos_value(pos);
       self.update_hash(pos, player);
+      self.putting_allowed_count -= 1;
       match self.get_empty_base_player(pos) {
         Some(empty_base_player) => {
           self.just_put_point(pos, player);
           if empty_base_player == player {
             self.clear_empty_base(pos);
           } else {
-            if self.find_captures(pos, player) {
+            if self.find_captures(pos, player, surround) {
               self.remove_empty_base(pos);
             } else {
               let next_player = player.next();
@@ -888,7 +1265,7 @@ impl Field {
         },
         None => {
           self.just_put_point(pos, player);
-          self.find_captures(pos, player);
+          self.find_captures(pos, player, surround);
         }
       }
       self.points_seq.push(pos);
@@ -898,27 +1275,146 @@ impl Field {
     }
   }
 
-  pub fn undo(&mut self) -> bool {
-    if let Some(change) = self.changes.pop() {
-      self.points_seq.pop();
-      self.score_red = change.score_red;
-      self.score_black = change.score_black;
-      self.hash = change.hash;
-      for (pos, cell) in change.points_changes.into_iter().rev() {
-        self.points[pos] = cell;
+  // Passes the move. Two passes in a row end the game.
+  pub fn pass(&mut self, player: Player) -> bool {
+    if !self.is_game_over() {
+      self.clear_redo();
+      let change = self.new_change();
+      self.changes.push(change);
+      self.points_seq.push(pass_pos(player));
+      true
+    } else {
+      false
+    }
+  }
+
+  fn new_change(&self) -> FieldChange {
+    FieldChange {
+      score_red: self.score_red,
+      score_black: self.score_black,
+      hash: self.hash,
+      points_changes: Vec::new(),
+      dsu_changes: Vec::new(),
+      dsu_size_change: None,
+      putting_allowed_count: self.putting_allowed_count,
+      captures: Vec::new()
+    }
+  }
+
+  // Exchanges values saved in the change with the current ones, so applying the same change twice restores the field.
+  // Saved values are exchanged in reverse order on undo and in direct order on redo, so repeated positions are handled correctly.
+  fn swap_change(&mut self, change: &mut FieldChange, reverse: bool) {
+    mem::swap(&mut self.score_red, &mut change.score_red);
+    mem::swap(&mut self.score_black, &mut change.score_black);
+    mem::swap(&mut self.putting_allowed_count, &mut change.putting_allowed_count);
+    mem::swap(&mut self.hash, &mut change.hash);
+    if let Some((pos, ref mut dsu_size)) = change.dsu_size_change {
+      mem::swap(&mut self.dsu_size[pos], dsu_size);
+    }
+    if reverse {
+      for &mut (pos, ref mut cell) in change.points_changes.iter_mut().rev() {
+        mem::swap(&mut self.points[pos], cell);
+      }
+      for &mut (pos, ref mut dsu_value) in change.dsu_changes.iter_mut().rev() {
+        mem::swap(&mut self.dsu[pos], dsu_value);
       }
-      for (pos, dsu_value) in change.dsu_changes.into_iter().rev() {
-        self.dsu[pos] = dsu_value;
+    } else {
+      for &mut (pos, ref mut dsu_value) in change.dsu_changes.iter_mut() {
+        mem::swap(&mut self.dsu[pos], dsu_value);
       }
-      if let Some((pos, dsu_size)) = change.dsu_size_change {
-        self.dsu_size[pos] = dsu_size;
+      for &mut (pos, ref mut cell) in change.points_changes.iter_mut() {
+        mem::swap(&mut self.points[pos], cell);
       }
+    }
+  }
+
+  fn undo_generic(&mut self, keep_for_redo: bool) -> bool {
+    if !keep_for_redo {
+      self.clear_redo();
+    }
+    if let Some(player) = self.resigned {
+      self.resigned = None;
+      if keep_for_redo {
+        self.redo_resigned = Some(player);
+      }
+      true
+    } else if let Some(player) = self.grounded {
+      self.grounded = None;
+      if keep_for_redo {
+        self.redo_grounded = Some(player);
+      }
+      true
+    } else if let Some(mut change) = self.changes.pop() {
+      let pos = self.points_seq.pop().unwrap();
+      self.swap_change(&mut change, true);
+      if keep_for_redo {
+        self.redo_changes.push((pos, change));
+      }
+      true
+    } else {
+      false
+    }
+  }
+
+  // Undoes the resignation or the grounding if the game was ended by them, otherwise undoes the last move.
+  // The undone move is dropped together with all moves kept for redo, so searches can undo moves without storing them.
+  pub fn undo(&mut self) -> bool {
+    self.undo_generic(false)
+  }
+
+  // The same as undo, but the undone move is kept for redo.
+  pub fn undo_for_redo(&mut self) -> bool {
+    self.undo_generic(true)
+  }
+
+  // Puts the last undone move back, the undone grounding or resignation is redone after all moves. Any new move clears undone moves.
+  pub fn redo(&mut self) -> bool {
+    if self.grounded.is_some() || self.resigned.is_some() {
+      return false;
+    }
+    if let Some((pos, mut change)) = self.redo_changes.pop() {
+      self.swap_change(&mut change, false);
+      self.changes.push(change);
+      self.points_seq.push(pos);
+      true
+    } else if let Some(player) = self.redo_resigned.take() {
+      self.resigned = Some(player);
+      true
+    } else if let Some(player) = self.redo_grounded.take() {
+      self.grounded = Some(player);
       true
     } else {
       false
     }
   }
 
+  #[inline]
+  pub fn redo_count(&self) -> usize {
+    self.redo_changes.len()
+  }
+
+  // Drops undone moves kept for redo.
+  pub fn clear_redo(&mut self) {
+    self.redo_changes.clear();
+    self.redo_grounded = None;
+    self.redo_resigned = None;
+  }
+
+  // Moves through the game history to the position after move_number moves, undoing or redoing moves as needed.
+  pub fn goto(&mut self, move_number: usize) -> bool {
+    let moves_count = self.moves_count();
+    if move_number > moves_count && (move_number > moves_count + self.redo_count() || self.grounded.is_some() || self.resigned.is_some()) {
+      return false;
+    }
+    while self.moves_count() > move_number {
+      self.undo_for_redo();
+    }
+    while self.moves_count() < move_number {
+      self.redo();
+    }
+    true
+  }
+
   #[inline]
   pub fn moves_count(&self) -> usize {
     self.points_seq.len()
@@ -948,7 +1444,24 @@ impl Field {
 
   #[inline]
   pub fn last_player(&self) -> Option<Player> {
-    self.points_seq.last().map(|&pos| self.get_player(pos))
+    self.points_seq.last().map(|&pos| if is_pass(pos) { Player::from_bool(pos == 1) } else { self.get_player(pos) })
+  }
+
+  // Captures made by the last move, including a capture of the point itself when it is put into an enemy empty base.
+  #[inline]
+  pub fn last_captures(&self) -> &[CaptureEvent] {
+    self.changes.last().map_or(&[][..], |change| &change.captures[..])
+  }
+
+  // Captures made by the move with the given index in the moves sequence.
+  #[inline]
+  pub fn captures_at(&self, move_index: usize) -> Option<&[CaptureEvent]> {
+    self.changes.get(move_index).map(|change| &change.captures[..])
+  }
+
+  #[inline]
+  pub fn is_last_move_pass(&self) -> bool {
+    self.points_seq.last().map_or(false, |&pos| is_pass(pos))
   }
 
   #[inline]
@@ -992,7 +1505,278 @@ impl Field {
     })
   }
 
+  // Returns the padding border and unplayable cells connected to it through unplayable cells, so cut corners and irregular
+  // outlines of the board are its edge while holes inside the board are not.
+  fn outer_cells(&self) -> Vec<bool> {
+    let row_length = self.width as Pos + 2;
+    let mut outer = iter::repeat(false).take(self.length).collect::<Vec<bool>>();
+    let mut queue = LinkedList::new();
+    for pos in 0 .. self.length {
+      let x = pos % row_length;
+      let y = pos / row_length;
+      if x == 0 || x == row_length - 1 || y == 0 || y == self.height as Pos + 1 {
+        outer[pos] = true;
+      } else if self.is_bad(pos) && (x == 1 || x == self.width as Pos || y == 1 || y == self.height as Pos) {
+        outer[pos] = true;
+        queue.push_back(pos);
+      }
+    }
+    while let Some(pos) = queue.pop_front() {
+      for &next_pos in &[self.n(pos), self.s(pos), self.w(pos), self.e(pos)] {
+        if !outer[next_pos] && self.is_bad(next_pos) {
+          outer[next_pos] = true;
+          queue.push_back(next_pos);
+        }
+      }
+    }
+    outer
+  }
+
+  #[inline]
+  fn is_edge(&self, pos: Pos, outer: &[bool]) -> bool {
+    outer[self.n(pos)] || outer[self.s(pos)] || outer[self.w(pos)] || outer[self.e(pos)]
+  }
+
+  // A torus has no edge, so there every point is grounded and the game can't be ended by grounding.
+  #[inline]
+  pub fn has_edge(&self) -> bool {
+    self.rules.topology != Topology::Torus
+  }
+
+  // Returns live points of the player that are not connected through a chain of his live points to the board edge.
+  pub fn ungrounded_points(&self, player: Player) -> Vec<Pos> {
+    if !self.has_edge() {
+      return Vec::new();
+    }
+    let outer = self.outer_cells();
+    let mut grounded = iter::repeat(false).take(self.length).collect::<Vec<bool>>();
+    let mut queue = LinkedList::new();
+    for pos in self.min_pos() .. self.max_pos() + 1 {
+      if self.is_live_players_point(pos, player) && self.is_edge(pos, &outer) {
+        grounded[pos] = true;
+        queue.push_back(pos);
+      }
+    }
+    while let Some(pos) = queue.pop_front() {
+      for &next_pos in &[self.n(pos), self.s(pos), self.w(pos), self.e(pos), self.nw(pos), self.ne(pos), self.sw(pos), self.se(pos)] {
+        if !grounded[next_pos] && self.is_live_players_point(next_pos, player) {
+          grounded[next_pos] = true;
+          queue.push_back(next_pos);
+        }
+      }
+    }
+    (self.min_pos() .. self.max_pos() + 1).filter(|&pos| !grounded[pos] && self.is_live_players_point(pos, player)).collect()
+  }
+
+  // Returns every move of the player that completes a surrounding together with enemy points it would capture.
+  pub fn surround_moves(&self, player: Player) -> Vec<(Pos, Vec<Pos>)> {
+    let mut result = Vec::new();
+    if self.is_game_over() {
+      return result;
+    }
+    let mut field = self.clone();
+    for pos in self.min_pos() .. self.max_pos() + 1 {
+      if self.is_putting_allowed(pos) && self.get_input_points(pos, player).len() > 1 && field.put_point(pos, player) {
+        let captured = field.last_captures().iter().filter(|capture| capture.player == player).flat_map(|capture| capture.captured.iter().cloned()).collect::<Vec<Pos>>();
+        if !captured.is_empty() {
+          result.push((pos, captured));
+        }
+        field.undo();
+      }
+    }
+    result
+  }
+
+  // Ends the game by grounding: all ungrounded points of the player are lost. Undo cancels grounding.
+  pub fn ground(&mut self, player: Player) -> bool {
+    if !self.is_game_over() && self.has_edge() {
+      self.clear_redo();
+      self.grounded = Some(player);
+      true
+    } else {
+      false
+    }
+  }
+
+  #[inline]
+  pub fn grounded_player(&self) -> Option<Player> {
+    self.grounded
+  }
+
+  // Ends the game by resignation of the player. Undo cancels resignation.
+  pub fn resign(&mut self, player: Player) -> bool {
+    if !self.is_game_over() {
+      self.clear_redo();
+      self.resigned = Some(player);
+      true
+    } else {
+      false
+    }
+  }
+
+  #[inline]
+  pub fn resigned_player(&self) -> Option<Player> {
+    self.resigned
+  }
+
+  #[inline]
+  pub fn putting_allowed_count(&self) -> usize {
+    self.putting_allowed_count
+  }
+
+  #[inline]
+  pub fn is_game_over(&self) -> bool {
+    self.grounded.is_some() || self.resigned.is_some() || self.putting_allowed_count == 0 || self.is_double_pass()
+  }
+
+  #[inline]
+  fn is_double_pass(&self) -> bool {
+    let moves_count = self.moves_count();
+    moves_count >= 2 && is_pass(self.points_seq[moves_count - 1]) && is_pass(self.points_seq[moves_count - 2])
+  }
+
+  pub fn result(&self) -> Option<GameResult> {
+    if let Some(resigned_player) = self.resigned {
+      let winner = resigned_player.next();
+      Some(GameResult {
+        winner: Some(winner),
+        score: cmp::max(self.final_score(winner), 0),
+        resignation: true
+      })
+    } else if self.is_game_over() {
+      let score_red = self.final_score(Player::Red);
+      Some(GameResult {
+        winner: if score_red > 0 { Some(Player::Red) } else if score_red < 0 { Some(Player::Black) } else { None },
+        score: score_red.abs(),
+        resignation: false
+      })
+    } else {
+      None
+    }
+  }
+
+  // Points of the player that are lost at the end of the game. With the grounding rule the grounded player loses his ungrounded points,
+  // if the game ended because there are no moves left both players lose them.
+  pub fn final_lost_points(&self, player: Player) -> Vec<Pos> {
+    match self.grounded {
+      Some(grounded_player) if grounded_player == player => self.ungrounded_points(player),
+      Some(_) => Vec::new(),
+      None if self.rules.grounding => self.ungrounded_points(player),
+      None => Vec::new()
+    }
+  }
+
+  // Score at the end of the game, every lost point is given to the enemy.
+  pub fn final_score(&self, player: Player) -> i32 {
+    self.score(player) - self.final_lost_points(player).len() as i32 + self.final_lost_points(player.next()).len() as i32
+  }
+
+  #[inline]
+  pub fn rules(&self) -> Rules {
+    self.rules
+  }
+
   pub fn zobrist(&self) -> &Zobrist {
     &self.zobrist
   }
+
+  // Serializes the field with the whole history of moves, including undone moves kept for redo.
+  // Zobrist hashes are not included, so the field must be restored with the same ones.
+  pub fn snapshot(&self) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(self.length * 9 + self.changes.len() * 64);
+    for &byte in SNAPSHOT_MAGIC {
+      snapshot::write_u8(&mut bytes, byte);
+    }
+    snapshot::write_u8(&mut bytes, SNAPSHOT_VERSION);
+    snapshot::write_u32(&mut bytes, self.width);
+    snapshot::write_u32(&mut bytes, self.height);
+    snapshot::write_u8(&mut bytes, self.rules.empty_base_capture as u8);
+    snapshot::write_u8(&mut bytes, (self.rules.surround == SurroundRule::Manual) as u8);
+    snapshot::write_u8(&mut bytes, (self.rules.base_in_base == BaseInBaseRule::Keep) as u8);
+    snapshot::write_u8(&mut bytes, self.rules.grounding as u8);
+    snapshot::write_u8(&mut bytes, (self.rules.topology == Topology::Torus) as u8);
+    snapshot::write_i32(&mut bytes, self.score_red);
+    snapshot::write_i32(&mut bytes, self.score_black);
+    snapshot::write_u64(&mut bytes, self.hash);
+    snapshot::write_u32(&mut bytes, self.putting_allowed_count as u32);
+    snapshot::write_u8(&mut bytes, self.grounded.map_or(0, |player| player.to_bool() as u8 + 1));
+    snapshot::write_u8(&mut bytes, self.resigned.map_or(0, |player| player.to_bool() as u8 + 1));
+    snapshot::write_u8(&mut bytes, self.redo_grounded.map_or(0, |player| player.to_bool() as u8 + 1));
+    snapshot::write_u8(&mut bytes, self.redo_resigned.map_or(0, |player| player.to_bool() as u8 + 1));
+    for &cell in &self.points {
+      snapshot::write_u8(&mut bytes, cell.to_value());
+    }
+    for &dsu_value in &self.dsu {
+      snapshot::write_u32(&mut bytes, dsu_value as u32);
+    }
+    for &dsu_size in &self.dsu_size {
+      snapshot::write_u32(&mut bytes, dsu_size);
+    }
+    snapshot::write_u32(&mut bytes, self.points_seq.len() as u32);
+    for (&pos, change) in self.points_seq.iter().zip(self.changes.iter()) {
+      snapshot::write_u32(&mut bytes, pos as u32);
+      change.write(&mut bytes);
+    }
+    snapshot::write_u32(&mut bytes, self.redo_changes.len() as u32);
+    for &(pos, ref change) in &self.redo_changes {
+      snapshot::write_u32(&mut bytes, pos as u32);
+      change.write(&mut bytes);
+    }
+    bytes
+  }
+
+  pub fn restore(bytes: &[u8], zobrist: Arc<Zobrist>) -> Result<Field, &'static str> {
+    if bytes.len() < SNAPSHOT_MAGIC.len() || &bytes[.. SNAPSHOT_MAGIC.len()] != &SNAPSHOT_MAGIC[..] {
+      return Err("Not a field snapshot!");
+    }
+    let mut reader = SnapshotReader::new(&bytes[SNAPSHOT_MAGIC.len() ..]);
+    if try!(reader.read_u8()) != SNAPSHOT_VERSION {
+      return Err("Unsupported snapshot version!");
+    }
+    let width = try!(reader.read_u32());
+    let height = try!(reader.read_u32());
+    if width == 0 || height == 0 || (width as u64 + 2) * (height as u64 + 2) * 9 > bytes.len() as u64 {
+      return Err("Invalid field size in snapshot!");
+    }
+    let rules = Rules {
+      empty_base_capture: try!(reader.read_bool()),
+      surround: if try!(reader.read_bool()) { SurroundRule::Manual } else { SurroundRule::Automatic },
+      base_in_base: if try!(reader.read_bool()) { BaseInBaseRule::Keep } else { BaseInBaseRule::Free },
+      grounding: try!(reader.read_bool()),
+      topology: if try!(reader.read_bool()) { Topology::Torus } else { Topology::Plane }
+    };
+    let mut field = Field::new(width, height, zobrist, rules);
+    let length = field.length;
+    field.score_red = try!(reader.read_i32());
+    field.score_black = try!(reader.read_i32());
+    field.hash = try!(reader.read_u64());
+    field.putting_allowed_count = try!(read_pos(&mut reader, length + 1));
+    field.grounded = try!(read_player_option(&mut reader));
+    field.resigned = try!(read_player_option(&mut reader));
+    field.redo_grounded = try!(read_player_option(&mut reader));
+    field.redo_resigned = try!(read_player_option(&mut reader));
+    for pos in 0 .. length {
+      field.points[pos] = try!(read_cell(&mut reader));
+    }
+    for pos in 0 .. length {
+      field.dsu[pos] = try!(read_pos(&mut reader, length));
+    }
+    for pos in 0 .. length {
+      field.dsu_size[pos] = try!(reader.read_u32());
+    }
+    let moves_count = try!(reader.read_u32());
+    for _ in 0 .. moves_count {
+      field.points_seq.push(try!(read_pos(&mut reader, length)));
+      field.changes.push(try!(FieldChange::read(&mut reader, length)));
+    }
+    let redo_count = try!(reader.read_u32());
+    for _ in 0 .. redo_count {
+      let pos = try!(read_pos(&mut reader, length));
+      field.redo_changes.push((pos, try!(FieldChange::read(&mut reader, length))));
+    }
+    if !reader.is_finished() {
+      return Err("Unexpected data at the end of snapshot!");
+    }
+    Ok(field)
+  }
 }
@@ -1,28 +1,39 @@
-use std::ascii::AsciiExt;
 use std::sync::Arc;
 use rand::{Rng, XorShiftRng, SeedableRng};
 use quickcheck;
 use quickcheck::TestResult;
+use time;
 use player::Player;
 use zobrist::Zobrist;
 use field;
-use field::{Pos, Field};
+use field::{Pos, Field, GameResult};
+use image;
+use rules::{Rules, SurroundRule, BaseInBaseRule, Topology};
 
 fn construct_field(image: &str) -> Field {
-  let lines = image.split('\n').map(|line| line.trim_matches(' ')).filter(|line| !line.is_empty()).collect::<Vec<&str>>();
-  let height = lines.len() as u32;
-  assert!(height > 0);
-  let width = lines.first().unwrap().len() as u32;
-  assert!(lines.iter().all(|line| line.len() as u32 == width));
-  let mut moves = lines.into_iter().enumerate().flat_map(|(y, line)|
-    line.chars().enumerate().filter(|&(_, c)| c.to_ascii_lowercase() != c.to_ascii_uppercase()).map(move |(x, c)| (c, x as u32, y as u32))
-  ).collect::<Vec<(char, u32, u32)>>();
-  moves.sort_by(|&(c1, _, _), &(c2, _, _)| (c1.to_ascii_lowercase(), c1.is_lowercase()).cmp(&(c2.to_ascii_lowercase(), c2.is_lowercase())));
+  construct_field_with_rules(image, Rules::default())
+}
+
+fn construct_field_with_rules(image: &str, rules: Rules) -> Field {
+  let (width, height, moves) = image::parse(image).unwrap();
+  let mut rng = XorShiftRng::new_unseeded();
+  let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
+  let mut field = Field::new(width, height, zobrist, rules);
+  for (x, y, player) in moves.into_iter() {
+    let pos = field.to_pos(x, y);
+    field.put_point(pos, player);
+  }
+  field
+}
+
+fn construct_field_with_mask(image: &str, mask_image: &str) -> Field {
+  let (width, height, moves) = image::parse(image).unwrap();
+  let (mask_width, mask_height, mask) = image::parse_mask(mask_image).unwrap();
+  assert_eq!((mask_width, mask_height), (width, height));
   let mut rng = XorShiftRng::new_unseeded();
   let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
-  let mut field = Field::new(width, height, zobrist);
-  for (c, x, y) in moves.into_iter() {
-    let player = Player::from_bool(c.is_uppercase());
+  let mut field = Field::with_mask(width, height, zobrist, Rules::default(), &mask);
+  for (x, y, player) in moves.into_iter() {
     let pos = field.to_pos(x, y);
     field.put_point(pos, player);
   }
@@ -281,9 +292,122 @@ fn three_surroundings_with_common_borders() {
   assert_eq!(field.captured_count(Player::Black), 0);
 }
 
+fn without_redo(field: &Field) -> Field {
+  let mut result = field.clone();
+  result.clear_redo();
+  result
+}
+
+fn undo_check_prop(width_seed: u32, height_seed: u32, seed: u64, rules: Rules) -> TestResult {
+  let width = width_seed % 30;
+  let height = height_seed % 30;
+  if width < 3 || height < 3 {
+    return TestResult::discard();
+  }
+  let seed_array = [3, seed as u32, 7, (seed >> 32) as u32];
+  let mut rng = XorShiftRng::from_seed(seed_array);
+  let mut moves = (field::min_pos(width) .. field::max_pos(width, height)).collect::<Vec<Pos>>();
+  rng.shuffle(&mut moves);
+  let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
+  let mut field = Field::new(width, height, zobrist, rules);
+  let mut player = Player::Red;
+  for pos in moves {
+    if field.is_putting_allowed(pos) {
+      player = player.next();
+      let surround = rules.surround == SurroundRule::Automatic || rng.gen();
+      let field_before = field.clone();
+      if surround {
+        field.put_point(pos, player);
+      } else {
+        field.put_point_without_surround(pos, player);
+      }
+      let field_after = field.clone();
+      field.undo();
+      if field_before != field {
+        return TestResult::failed();
+      }
+      if surround {
+        field.put_point(pos, player);
+      } else {
+        field.put_point_without_surround(pos, player);
+      }
+      field.undo_for_redo();
+      if field.redo_count() != 1 || without_redo(&field) != field_before {
+        return TestResult::failed();
+      }
+      field.redo();
+      if field_after != field {
+        return TestResult::failed();
+      }
+      field.undo();
+      if surround {
+        field.put_point(pos, player);
+      } else {
+        field.put_point_without_surround(pos, player);
+      }
+      if field_after != field {
+        return TestResult::failed();
+      }
+    }
+  }
+  if field.putting_allowed_count() != (0 .. field.length()).filter(|&pos| field.is_putting_allowed(pos)).count() {
+    return TestResult::failed();
+  }
+  TestResult::passed()
+}
+
 #[test]
 fn undo_check() {
   fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules::default())
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+#[test]
+fn undo_check_without_empty_base_capture() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules { empty_base_capture: false, .. Rules::default() })
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+#[test]
+fn undo_check_manual_surround() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules { surround: SurroundRule::Manual, .. Rules::default() })
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+#[test]
+fn undo_check_keep_base_in_base() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules { base_in_base: BaseInBaseRule::Keep, .. Rules::default() })
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+#[test]
+fn undo_check_torus() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules { topology: Topology::Torus, .. Rules::default() })
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+#[test]
+fn undo_check_torus_manual_surround() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64) -> TestResult {
+    undo_check_prop(width_seed, height_seed, seed, Rules { topology: Topology::Torus, surround: SurroundRule::Manual, .. Rules::default() })
+  }
+  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+}
+
+// A game on a torus shifted by any vector must give the same scores after every move.
+#[test]
+fn torus_shift_invariance() {
+  fn prop(width_seed: u32, height_seed: u32, seed: u64, shift_x: u32, shift_y: u32) -> TestResult {
     let width = width_seed % 30;
     let height = height_seed % 30;
     if width < 3 || height < 3 {
@@ -291,24 +415,609 @@ fn undo_check() {
     }
     let seed_array = [3, seed as u32, 7, (seed >> 32) as u32];
     let mut rng = XorShiftRng::from_seed(seed_array);
-    let mut moves = (field::min_pos(width) .. field::max_pos(width, height)).collect::<Vec<Pos>>();
+    let mut moves = (0 .. width * height).map(|i| (i % width, i / width)).collect::<Vec<(u32, u32)>>();
     rng.shuffle(&mut moves);
     let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
-    let mut field = Field::new(width, height, zobrist);
+    let rules = Rules { topology: Topology::Torus, .. Rules::default() };
+    let mut field = Field::new(width, height, zobrist.clone(), rules);
+    let mut shifted_field = Field::new(width, height, zobrist, rules);
     let mut player = Player::Red;
-    for pos in moves {
+    for (x, y) in moves {
+      let pos = field.to_pos(x, y);
+      let shifted_pos = shifted_field.to_pos((x + shift_x % width) % width, (y + shift_y % height) % height);
+      if field.is_putting_allowed(pos) != shifted_field.is_putting_allowed(shifted_pos) {
+        return TestResult::failed();
+      }
       if field.is_putting_allowed(pos) {
         player = player.next();
-        let field_before = field.clone();
         field.put_point(pos, player);
-        field.undo();
-        if field_before != field {
+        shifted_field.put_point(shifted_pos, player);
+        if field.score(Player::Red) != shifted_field.score(Player::Red) {
           return TestResult::failed();
         }
-        field.put_point(pos, player);
       }
     }
     TestResult::passed()
   }
-  quickcheck::quickcheck(prop as fn(u32, u32, u64) -> TestResult);
+  quickcheck::quickcheck(prop as fn(u32, u32, u64, u32, u32) -> TestResult);
+}
+
+#[test]
+fn torus_surround_across_edges() {
+  let image = "
+    Bb..b
+    b....
+    .....
+    .....
+    b....
+    ";
+  let field = construct_field_with_rules(image, Rules { topology: Topology::Torus, .. Rules::default() });
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert!(field.is_captured(field.to_pos(0, 0)));
+  let field = construct_field(image);
+  assert_eq!(field.captured_count(Player::Red), 0);
+}
+
+#[test]
+fn torus_empty_base_across_edges() {
+  let field = construct_field_with_rules(
+    "
+    Ba..a
+    a....
+    .....
+    .....
+    a....
+    ",
+    Rules { topology: Topology::Torus, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert!(field.is_captured(field.to_pos(0, 0)));
+}
+
+#[test]
+fn torus_ring_around_the_field_does_not_surround() {
+  let field = construct_field_with_rules(
+    "
+    .....
+    aaaaa
+    ..B..
+    aaaaa
+    .....
+    ",
+    Rules { topology: Topology::Torus, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 0);
+  assert!(!field.is_captured(field.to_pos(2, 2)));
+  assert!(field.is_putting_allowed(field.to_pos(0, 2)));
+  assert!(!field.is_empty_base(field.to_pos(0, 2)));
+}
+
+#[test]
+fn torus_has_no_grounding() {
+  let mut field = construct_field_with_rules(
+    "
+    .....
+    .a...
+    .....
+    ...B.
+    .....
+    ",
+    Rules { topology: Topology::Torus, grounding: true, .. Rules::default() }
+  );
+  assert!(field.ungrounded_points(Player::Red).is_empty());
+  assert!(field.ungrounded_points(Player::Black).is_empty());
+  assert_eq!(field.final_score(Player::Red), field.score(Player::Red));
+  assert!(!field.ground(Player::Red));
+  assert!(!field.is_game_over());
+}
+
+#[test]
+fn no_empty_base_capture() {
+  let field = construct_field_with_rules(
+    "
+    .a.
+    aBa
+    .a.
+    ",
+    Rules { empty_base_capture: false, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 0);
+  assert_eq!(field.captured_count(Player::Black), 0);
+  assert!(!field.is_captured(field.to_pos(1, 1)));
+}
+
+#[test]
+fn no_empty_base_capture_leaves_no_empty_base() {
+  let field = construct_field_with_rules(
+    "
+    .a.
+    a.a
+    .a.
+    ",
+    Rules { empty_base_capture: false, .. Rules::default() }
+  );
+  assert!(field.is_putting_allowed(field.to_pos(1, 1)));
+  assert!(!field.is_empty_base(field.to_pos(1, 1)));
+}
+
+#[test]
+fn no_empty_base_capture_still_surrounds() {
+  let field = construct_field_with_rules(
+    "
+    .a.
+    cBa
+    .a.
+    ",
+    Rules { empty_base_capture: false, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert_eq!(field.captured_count(Player::Black), 0);
+}
+
+#[test]
+fn manual_surround_declared() {
+  let field = construct_field_with_rules(
+    "
+    .a.
+    cBa
+    .a.
+    ",
+    Rules { surround: SurroundRule::Manual, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert!(field.is_captured(field.to_pos(1, 1)));
+}
+
+#[test]
+fn manual_surround_not_declared() {
+  let mut field = construct_field_with_rules(
+    "
+    .a.
+    .Ba
+    .a.
+    ",
+    Rules { surround: SurroundRule::Manual, .. Rules::default() }
+  );
+  let pos = field.to_pos(0, 1);
+  assert!(field.put_point_without_surround(pos, Player::Red));
+  assert_eq!(field.captured_count(Player::Red), 0);
+  assert!(!field.is_captured(field.to_pos(1, 1)));
+}
+
+#[test]
+fn undeclared_surround_is_not_allowed_with_automatic_surround() {
+  let mut field = construct_field(
+    "
+    .a.
+    .Ba
+    .a.
+    "
+  );
+  let pos = field.to_pos(0, 1);
+  assert!(!field.put_point_without_surround(pos, Player::Red));
+  assert!(field.is_putting_allowed(pos));
+}
+
+#[test]
+fn keep_base_in_base() {
+  let field = construct_field_with_rules(
+    "
+    ...c...
+    ..cBc..
+    .cBaBc.
+    ..cBc..
+    ...c...
+    ",
+    Rules { base_in_base: BaseInBaseRule::Keep, .. Rules::default() }
+  );
+  assert_eq!(field.captured_count(Player::Red), 4);
+  assert_eq!(field.captured_count(Player::Black), 1);
+  assert!(field.is_captured(field.to_pos(3, 2)));
+}
+
+#[test]
+fn ungrounded_points() {
+  let field = construct_field(
+    "
+    a.....
+    .b..a.
+    ....a.
+    ......
+    "
+  );
+  assert_eq!(field.ungrounded_points(Player::Red), vec![field.to_pos(4, 1), field.to_pos(4, 2)]);
+  assert_eq!(field.ungrounded_points(Player::Black), vec![field.to_pos(1, 1)]);
+}
+
+#[test]
+fn only_outer_holes_are_edges() {
+  let field = construct_field_with_mask(
+    "
+    .....
+    ..a..
+    .....
+    .a...
+    .....
+    ",
+    "
+    .....
+    .....
+    ..-..
+    -....
+    .....
+    "
+  );
+  assert_eq!(field.ungrounded_points(Player::Red), vec![field.to_pos(2, 1)]);
+}
+
+#[test]
+fn grounding_ends_game() {
+  let mut field = construct_field(
+    "
+    a.....
+    ....a.
+    .B....
+    ......
+    "
+  );
+  assert!(!field.is_game_over());
+  assert!(field.ground(Player::Red));
+  assert!(field.is_game_over());
+  assert!(!field.ground(Player::Black));
+  let pos = field.to_pos(5, 3);
+  assert!(!field.put_point(pos, Player::Black));
+  assert_eq!(field.final_score(Player::Red), -1);
+  assert_eq!(field.final_score(Player::Black), 1);
+  assert!(field.undo_for_redo());
+  assert!(!field.is_game_over());
+  assert_eq!(field.moves_count(), 3);
+  assert!(field.redo());
+  assert_eq!(field.grounded_player(), Some(Player::Red));
+  assert_eq!(field.moves_count(), 3);
+}
+
+#[test]
+fn grounding_rule_at_game_end() {
+  let field = construct_field_with_rules(
+    "
+    ......
+    .a..B.
+    ......
+    a.....
+    ",
+    Rules { grounding: true, .. Rules::default() }
+  );
+  assert_eq!(field.final_score(Player::Red), 0);
+  assert_eq!(field.score(Player::Red), 0);
+  let field = construct_field_with_rules(
+    "
+    ......
+    .a..B.
+    .a....
+    ......
+    ",
+    Rules { grounding: true, .. Rules::default() }
+  );
+  assert_eq!(field.final_score(Player::Red), -1);
+  assert_eq!(field.final_score(Player::Black), 1);
+}
+
+#[test]
+fn game_result_when_no_moves_left() {
+  let mut field = construct_field(
+    "
+    aaa
+    aBa
+    aaa
+    "
+  );
+  assert!(field.is_game_over());
+  assert_eq!(field.result(), Some(GameResult { winner: Some(Player::Red), score: 1, resignation: false }));
+  assert!(field.undo());
+  assert!(!field.is_game_over());
+  assert_eq!(field.result(), None);
+  let field = construct_field(
+    "
+    aB
+    Ba
+    "
+  );
+  assert_eq!(field.result(), Some(GameResult { winner: None, score: 0, resignation: false }));
+}
+
+#[test]
+fn redo_and_goto() {
+  let mut field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  let field_end = field.clone();
+  assert!(!field.redo());
+  assert!(field.goto(1));
+  assert_eq!(field.moves_count(), 1);
+  assert_eq!(field.redo_count(), 4);
+  assert_eq!(field.captured_count(Player::Red), 0);
+  assert!(!field.goto(6));
+  assert!(field.redo());
+  assert!(field.goto(5));
+  assert_eq!(image::write_marks(&field), image::write_marks(&field_end));
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert_eq!(field.hash(), field_end.hash());
+  assert!(field.goto(0));
+  assert_eq!(field.hash(), 0);
+  assert!(field.goto(3));
+  let pos = field.to_pos(0, 0);
+  assert!(field.put_point(pos, Player::Black));
+  assert_eq!(field.redo_count(), 0);
+  assert!(!field.goto(5));
+}
+
+#[test]
+fn two_passes_end_game() {
+  let mut field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  let hash = field.hash();
+  assert!(field.pass(Player::Black));
+  assert_eq!(field.last_player(), Some(Player::Black));
+  assert_eq!(field.cur_player(), Player::Red);
+  assert_eq!(field.hash(), hash);
+  assert!(!field.is_game_over());
+  assert!(field.pass(Player::Red));
+  assert!(field.is_game_over());
+  assert_eq!(field.result(), Some(GameResult { winner: Some(Player::Red), score: 1, resignation: false }));
+  assert!(!field.pass(Player::Black));
+  let pos = field.to_pos(0, 0);
+  assert!(!field.put_point(pos, Player::Black));
+  assert!(field.undo());
+  assert!(!field.is_game_over());
+  assert!(field.put_point(pos, Player::Red));
+  assert_eq!(field.moves_count(), 7);
+  assert_eq!(image::write(&field), "fa./dCb/.e.");
+}
+
+#[test]
+fn resignation_ends_game() {
+  let mut field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  let mut black_resigned = field.clone();
+  assert!(black_resigned.resign(Player::Black));
+  assert_eq!(black_resigned.result(), Some(GameResult { winner: Some(Player::Red), score: 1, resignation: true }));
+  assert!(field.resign(Player::Red));
+  assert!(field.is_game_over());
+  assert_eq!(field.result(), Some(GameResult { winner: Some(Player::Black), score: 0, resignation: true }));
+  assert!(!field.resign(Player::Black));
+  assert!(!field.pass(Player::Black));
+  assert!(!field.ground(Player::Black));
+  let restored = Field::restore(&field.snapshot(), Arc::new(field.zobrist().clone())).unwrap();
+  assert!(restored == field);
+  assert!(field.undo_for_redo());
+  assert!(!field.is_game_over());
+  assert_eq!(field.moves_count(), 5);
+  let mut restored = Field::restore(&field.snapshot(), Arc::new(field.zobrist().clone())).unwrap();
+  assert!(restored == field);
+  assert!(restored.redo());
+  assert_eq!(restored.resigned_player(), Some(Player::Red));
+  assert!(!restored.redo());
+  assert!(field.undo_for_redo());
+  assert!(field.redo());
+  assert!(field.redo());
+  assert_eq!(field.resigned_player(), Some(Player::Red));
+  assert!(field.undo_for_redo());
+  assert!(field.undo());
+  assert!(!field.redo());
+}
+
+#[test]
+fn capture_events() {
+  let mut field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  assert_eq!(field.last_captures().len(), 1);
+  let capture = field.last_captures()[0].clone();
+  assert_eq!(capture.player, Player::Red);
+  assert_eq!(capture.captured, vec![field.to_pos(1, 1)]);
+  assert!(capture.freed.is_empty());
+  let mut chain = capture.chain.clone();
+  chain.sort();
+  let mut expected_chain = vec![field.to_pos(1, 0), field.to_pos(0, 1), field.to_pos(2, 1), field.to_pos(1, 2)];
+  expected_chain.sort();
+  assert_eq!(chain, expected_chain);
+  assert_eq!(field.captures_at(3), Some(&[][..]));
+  assert!(field.undo_for_redo());
+  assert!(field.last_captures().is_empty());
+  assert!(field.redo());
+  assert_eq!(field.last_captures(), &[capture][..]);
+  let field = construct_field(
+    "
+    .a.
+    aBa
+    .a.
+    "
+  );
+  assert_eq!(field.last_player(), Some(Player::Black));
+  assert_eq!(field.last_captures().len(), 1);
+  assert_eq!(field.last_captures()[0].player, Player::Red);
+  assert_eq!(field.last_captures()[0].captured, vec![field.to_pos(1, 1)]);
+}
+
+#[test]
+fn groups_and_surround_moves() {
+  let field = construct_field(
+    "
+    .a.
+    aBa
+    ...
+    "
+  );
+  let red_group = field.group_of(field.to_pos(0, 1));
+  assert!(red_group.is_some());
+  assert_eq!(field.group_of(field.to_pos(2, 1)), red_group);
+  assert!(field.group_of(field.to_pos(1, 1)) != red_group);
+  assert_eq!(field.group_of(field.to_pos(1, 2)), None);
+  assert_eq!(field.group_members(field.to_pos(2, 1)), vec![field.to_pos(1, 0), field.to_pos(0, 1), field.to_pos(2, 1)]);
+  assert_eq!(field.group_members(field.to_pos(1, 1)), vec![field.to_pos(1, 1)]);
+  assert_eq!(field.surround_moves(Player::Red), vec![(field.to_pos(1, 2), vec![field.to_pos(1, 1)])]);
+  assert!(field.surround_moves(Player::Black).is_empty());
+  assert_eq!(field.moves_count(), 4);
+}
+
+#[test]
+fn snapshot_round_trip() {
+  let mut field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    e..
+    "
+  );
+  assert!(field.undo_for_redo());
+  let restored = Field::restore(&field.snapshot(), Arc::new(field.zobrist().clone())).unwrap();
+  assert!(restored == field);
+  let mut restored = restored;
+  assert!(restored.redo());
+  assert_eq!(restored.captured_count(Player::Red), 1);
+  assert!(restored.goto(0));
+  assert_eq!(restored.hash(), 0);
+  assert_eq!(image::write_marks(&restored), ".../.../.../...");
+}
+
+#[test]
+fn invalid_snapshot() {
+  let field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  let snapshot = field.snapshot();
+  assert!(Field::restore(&snapshot[.. snapshot.len() - 1], Arc::new(field.zobrist().clone())).is_err());
+  let mut extra_data = snapshot.clone();
+  extra_data.push(0);
+  assert!(Field::restore(&extra_data, Arc::new(field.zobrist().clone())).is_err());
+  assert!(Field::restore(b"OPAI", Arc::new(field.zobrist().clone())).is_err());
+}
+
+#[test]
+fn snapshot_with_wrong_version() {
+  let field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  let mut snapshot = field.snapshot();
+  snapshot[4] += 1;
+  assert_eq!(Field::restore(&snapshot, Arc::new(field.zobrist().clone())).err(), Some("Unsupported snapshot version!"));
+}
+
+#[test]
+fn surround_around_masked_cell() {
+  let mut field = construct_field_with_mask(
+    "
+    ..a..
+    .aBa.
+    .a.a.
+    ..a..
+    .....
+    ",
+    "
+    .....
+    .....
+    ..-..
+    .....
+    -....
+    "
+  );
+  assert_eq!(field.captured_count(Player::Red), 1);
+  assert_eq!(image::write_marks(&field), "..#../.#*#./.#-#./..#../-....");
+  assert!(!field.is_putting_allowed(field.to_pos(2, 2)));
+  let pos = field.to_pos(0, 4);
+  assert!(!field.put_point(pos, Player::Black));
+  assert_eq!(field.putting_allowed_count(), 25 - 2 - 7);
+  assert!(field.undo());
+  assert_eq!(field.putting_allowed_count(), 25 - 2 - 6);
+  assert_eq!(image::write_marks(&field), "..o../.oeo./.o-o./..o../-....");
+}
+
+#[test]
+fn image_round_trip() {
+  let field = construct_field(
+    "
+    .a.
+    cBa
+    .d.
+    "
+  );
+  assert_eq!(image::write(&field), ".a./dCb/.e.");
+  assert_eq!(image::write_marks(&field), ".#./#*#/.#.");
+  assert_eq!(image::write(&construct_field(image::write(&field).as_str())), image::write(&field));
+}
+
+#[test]
+fn long_game_image_round_trip() {
+  let mut rng = XorShiftRng::new_unseeded();
+  let zobrist = Arc::new(Zobrist::new(field::length(10, 8) * 2, &mut rng));
+  let mut field = Field::new(10, 8, zobrist, Rules::default());
+  let mut moves = vec![(2, 2, Player::Red), (2, 1, Player::Black)];
+  for i in 0 .. 24 {
+    moves.push((i % 10, 5 + i / 10, Player::from_bool(i % 2 == 0)));
+  }
+  moves.push((1, 2, Player::Black));
+  moves.push((3, 2, Player::Black));
+  moves.push((2, 3, Player::Black));
+  for &(x, y, player) in &moves {
+    let pos = field.to_pos(x, y);
+    assert!(field.put_point(pos, player));
+  }
+  assert!(field.is_captured(field.to_pos(2, 2)));
+  let image = image::write(&field);
+  let restored = construct_field(image.as_str());
+  assert_eq!(restored.points_seq(), field.points_seq());
+  assert_eq!(image::write_marks(&restored), image::write_marks(&field));
+  assert_eq!(restored.score(Player::Black), field.score(Player::Black));
+  assert_eq!(image::write(&restored), image);
+  assert_eq!(image::parse("aB/..;0,0"), None);
+  assert_eq!(image::parse("aB/..;1,0;0,0"), Some((2, 2, vec![(1, 0, Player::Black), (0, 0, Player::Red)])));
+}
+
+// Time to fill plane fields in random order, run with `cargo test --release -- --ignored --nocapture plane_field_speed`
+// before and after a change of neighbour lookups to compare.
+#[test]
+#[ignore]
+fn plane_field_speed() {
+  let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+  let zobrist = Arc::new(Zobrist::new(field::length(30, 30) * 2, &mut rng));
+  let mut moves = (field::min_pos(30) .. field::max_pos(30, 30) + 1).collect::<Vec<Pos>>();
+  let start_time = time::precise_time_ns();
+  for _ in 0 .. 1000 {
+    let mut field = Field::new(30, 30, zobrist.clone(), Rules::default());
+    rng.shuffle(&mut moves);
+    let mut player = Player::Red;
+    for &pos in &moves {
+      if field.put_point(pos, player) {
+        player = player.next();
+      }
+    }
+  }
+  println!("Filling 1000 fields took {} ms.", (time::precise_time_ns() - start_time) / 1000000);
 }
@@ -0,0 +1,134 @@
+use std::cmp;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use player::Player;
+use field::Pos;
+
+// Key of the side to move, the field hash doesn't depend on it.
+const BLACK_KEY: u64 = 0x9E37_79B9_7F4A_7C15;
+
+const MAX_DEPTH: u32 = 0xFF;
+
+const MAX_POS: Pos = 0x3F_FFFF;
+
+// Entries are packed into 64 bits and stored in AtomicUsize, so the build fails on targets with narrower pointers.
+#[cfg(not(target_pointer_width = "64"))]
+const HASH_TABLE_REQUIRES_64_BIT_TARGET: [(); 0] = [()];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HashBound {
+  // The value is exact.
+  Exact,
+  // The real value is greater than or equal to the value.
+  Lower,
+  // The real value is less than or equal to the value.
+  Upper
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct HashEntry {
+  pub depth: u32,
+  pub bound: HashBound,
+  pub value: i32,
+  pub best_move: Option<Pos>
+}
+
+impl HashEntry {
+  // Packs the entry into 64 bits: 32 bits of the value, 8 bits of the depth, 2 bits of the bound and 22 bits of the best move.
+  // Zero is never a packed entry since bound bits are not zero.
+  fn pack(&self) -> u64 {
+    let bound = match self.bound {
+      HashBound::Exact => 1u64,
+      HashBound::Lower => 2u64,
+      HashBound::Upper => 3u64
+    };
+    let best_move = self.best_move.map_or(0, |pos| if pos <= MAX_POS { pos as u64 } else { 0 });
+    self.value as u32 as u64 | (cmp::min(self.depth, MAX_DEPTH) as u64) << 32 | bound << 40 | best_move << 42
+  }
+
+  fn unpack(data: u64) -> Option<HashEntry> {
+    let bound = match (data >> 40) & 3 {
+      1 => HashBound::Exact,
+      2 => HashBound::Lower,
+      3 => HashBound::Upper,
+      _ => return None
+    };
+    let best_move = (data >> 42) as Pos;
+    Some(HashEntry {
+      depth: ((data >> 32) & 0xFF) as u32,
+      bound: bound,
+      value: data as u32 as i32,
+      best_move: if best_move == 0 { None } else { Some(best_move) }
+    })
+  }
+}
+
+struct HashSlot {
+  // Data xored with the key, so a slot torn by concurrent writes doesn't match the key.
+  check: AtomicUsize,
+  data: AtomicUsize
+}
+
+// Lock-free transposition table shared by minimax threads. Entries are packed into machine words.
+pub struct HashTable {
+  slots: Vec<HashSlot>
+}
+
+impl HashTable {
+  // The size is the number of entries, it's rounded up to a power of two.
+  pub fn new(size: usize) -> HashTable {
+    let size = cmp::max(size, 1).next_power_of_two();
+    HashTable {
+      slots: (0 .. size).map(|_| HashSlot { check: AtomicUsize::new(0), data: AtomicUsize::new(0) }).collect()
+    }
+  }
+
+  #[inline]
+  pub fn size(&self) -> usize {
+    self.slots.len()
+  }
+
+  #[inline]
+  fn key(hash: u64, player: Player) -> u64 {
+    if player == Player::Black { hash ^ BLACK_KEY } else { hash }
+  }
+
+  #[inline]
+  fn slot(&self, key: u64) -> &HashSlot {
+    &self.slots[key as usize & (self.slots.len() - 1)]
+  }
+
+  pub fn get(&self, hash: u64, player: Player) -> Option<HashEntry> {
+    let key = HashTable::key(hash, player);
+    let slot = self.slot(key);
+    let data = slot.data.load(Ordering::Relaxed);
+    if data != 0 && slot.check.load(Ordering::Relaxed) ^ data == key as usize {
+      HashEntry::unpack(data as u64)
+    } else {
+      None
+    }
+  }
+
+  // An entry of another position is always replaced, an entry of the same position is replaced only by a deeper or equal search.
+  pub fn put(&self, hash: u64, player: Player, entry: HashEntry) {
+    let key = HashTable::key(hash, player);
+    let slot = self.slot(key);
+    let old_data = slot.data.load(Ordering::Relaxed);
+    if old_data != 0 && slot.check.load(Ordering::Relaxed) ^ old_data == key as usize {
+      if let Some(old_entry) = HashEntry::unpack(old_data as u64) {
+        if old_entry.depth > entry.depth {
+          return;
+        }
+      }
+    }
+    let data = entry.pack() as usize;
+    slot.check.store(key as usize ^ data, Ordering::Relaxed);
+    slot.data.store(data, Ordering::Relaxed);
+  }
+
+  pub fn clear(&self) {
+    for slot in &self.slots {
+      slot.check.store(0, Ordering::Relaxed);
+      slot.data.store(0, Ordering::Relaxed);
+    }
+  }
+}
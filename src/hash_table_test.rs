@@ -0,0 +1,31 @@
+use player::Player;
+use hash_table::{HashTable, HashEntry, HashBound};
+
+#[test]
+fn store_and_load_entries() {
+  let hash_table = HashTable::new(1000);
+  assert_eq!(hash_table.size(), 1024);
+  let entry = HashEntry { depth: 5, bound: HashBound::Lower, value: -7, best_move: Some(42) };
+  hash_table.put(123456789, Player::Red, entry);
+  assert_eq!(hash_table.get(123456789, Player::Red), Some(entry));
+  assert_eq!(hash_table.get(123456789, Player::Black), None);
+  assert_eq!(hash_table.get(123456789 + 1024, Player::Red), None);
+  let entry = HashEntry { depth: 1, bound: HashBound::Exact, value: i32::max_value(), best_move: None };
+  hash_table.put(987654321, Player::Black, entry);
+  assert_eq!(hash_table.get(987654321, Player::Black), Some(entry));
+  hash_table.clear();
+  assert_eq!(hash_table.get(987654321, Player::Black), None);
+}
+
+#[test]
+fn deeper_entries_are_kept() {
+  let hash_table = HashTable::new(16);
+  let deep_entry = HashEntry { depth: 4, bound: HashBound::Upper, value: 3, best_move: Some(10) };
+  hash_table.put(1, Player::Red, deep_entry);
+  hash_table.put(1, Player::Red, HashEntry { depth: 2, bound: HashBound::Exact, value: 1, best_move: None });
+  assert_eq!(hash_table.get(1, Player::Red), Some(deep_entry));
+  let other_entry = HashEntry { depth: 1, bound: HashBound::Exact, value: 0, best_move: None };
+  hash_table.put(17, Player::Red, other_entry);
+  assert_eq!(hash_table.get(17, Player::Red), Some(other_entry));
+  assert_eq!(hash_table.get(1, Player::Red), None);
+}
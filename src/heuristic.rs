@@ -1,4 +1,5 @@
 use player::Player;
+use field;
 use field::{Pos, Field};
 
 static CG_SUM: [i32; 9] = [-5, -1, 0, 0, 1, 2, 5, 20, 30];
@@ -11,7 +12,7 @@ fn heuristic_estimation(field: &Field, pos: Pos, player: Player) -> i32 {
   let c2 = CG_SUM[field.number_near_points(pos, enemy) as usize];
   let mut result = (g1 * 3 + g2 * 2) * (5 - (g1 - g2).abs()) - c1 - c2;
   if let Some(&last_pos) = field.points_seq().last() {
-    if field.is_near(last_pos, pos) {
+    if !field::is_pass(last_pos) && field.is_near(last_pos, pos) {
       result += 5;
     }
   }
@@ -32,3 +33,10 @@ pub fn heuristic(field: &Field, player: Player) -> Option<Pos> {
   }
   result
 }
+
+pub fn heuristic_moves(field: &Field, player: Player, moves_count: usize) -> Vec<(Pos, i32)> {
+  let mut result = (field.min_pos() .. field.max_pos() + 1).filter(|&pos| field.is_putting_allowed(pos)).map(|pos| (pos, heuristic_estimation(field, pos, player))).collect::<Vec<(Pos, i32)>>();
+  result.sort_by(|&(_, estimation1), &(_, estimation2)| estimation2.cmp(&estimation1));
+  result.truncate(moves_count);
+  result
+}
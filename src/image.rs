@@ -0,0 +1,154 @@
+use std::ascii::AsciiExt;
+use std::char;
+use player::Player;
+use field;
+use field::{Pos, Field};
+
+const LETTERS_COUNT: usize = 26;
+
+// Splits an image into rows of the same length. Rows are separated by newlines or slashes.
+fn split_rows(image: &str) -> Option<(u32, u32, Vec<&str>)> {
+  let lines = image.split(|c| c == '\n' || c == '/').map(|line| line.trim_matches(' ')).filter(|line| !line.is_empty()).collect::<Vec<&str>>();
+  let height = lines.len() as u32;
+  if height == 0 {
+    return None;
+  }
+  let width = lines.first().unwrap().chars().count() as u32;
+  if lines.iter().all(|line| line.chars().count() as u32 == width) {
+    Some((width, height, lines))
+  } else {
+    None
+  }
+}
+
+#[inline]
+fn is_letter(c: char) -> bool {
+  c.to_ascii_lowercase() != c.to_ascii_uppercase()
+}
+
+// Parses the move order of the form "x,y;x,y;...", which must list every point of the image exactly once.
+fn parse_order(width: u32, height: u32, lines: &[&str], order: &str) -> Option<Vec<(u32, u32, Player)>> {
+  let cells = lines.iter().map(|line| line.chars().collect::<Vec<char>>()).collect::<Vec<Vec<char>>>();
+  let mut used = vec![false; (width * height) as usize];
+  let mut moves = Vec::new();
+  for move_str in order.split(';') {
+    let coordinates = move_str.split(',').map(|s| s.trim().parse::<u32>().ok()).collect::<Vec<Option<u32>>>();
+    if coordinates.len() != 2 {
+      return None;
+    }
+    let (x, y) = match (coordinates[0], coordinates[1]) {
+      (Some(x), Some(y)) if x < width && y < height => (x, y),
+      _ => return None
+    };
+    let c = cells[y as usize][x as usize];
+    let index = (y * width + x) as usize;
+    if !is_letter(c) || used[index] {
+      return None;
+    }
+    used[index] = true;
+    moves.push((x, y, Player::from_bool(c.is_uppercase())));
+  }
+  let points_count = cells.iter().map(|row| row.iter().filter(|&&c| is_letter(c)).count()).fold(0, |acc, count| acc + count);
+  if moves.len() == points_count {
+    Some(moves)
+  } else {
+    None
+  }
+}
+
+// Parses a field image where every letter is a point: uppercase letters are black points, lowercase letters are red points.
+// Points are put in alphabetical order, black before red for the same letter, and row by row for equal letters.
+// Rows are separated by newlines or slashes, any other non-letter character means an empty cell.
+// The image may be followed by ';' and the exact move order written by `write`, then letters mean only players of points.
+pub fn parse(image: &str) -> Option<(u32, u32, Vec<(u32, u32, Player)>)> {
+  let mut parts = image.splitn(2, ';');
+  let (width, height, lines) = match parts.next().and_then(split_rows) {
+    Some(result) => result,
+    None => return None
+  };
+  if let Some(order) = parts.next() {
+    return parse_order(width, height, &lines, order).map(|moves| (width, height, moves));
+  }
+  let mut moves = lines.into_iter().enumerate().flat_map(|(y, line)|
+    line.chars().enumerate().filter(|&(_, c)| is_letter(c)).map(move |(x, c)| (c, x as u32, y as u32))
+  ).collect::<Vec<(char, u32, u32)>>();
+  moves.sort_by(|&(c1, _, _), &(c2, _, _)| (c1.to_ascii_lowercase(), c1.is_lowercase()).cmp(&(c2.to_ascii_lowercase(), c2.is_lowercase())));
+  Some((width, height, moves.into_iter().map(|(c, x, y)| (x, y, Player::from_bool(c.is_uppercase()))).collect()))
+}
+
+// Parses a board shape where '-' is an unplayable cell and '.' is a playable one. The mask is returned row by row.
+pub fn parse_mask(image: &str) -> Option<(u32, u32, Vec<bool>)> {
+  let (width, height, lines) = match split_rows(image) {
+    Some(result) => result,
+    None => return None
+  };
+  let mut mask = Vec::with_capacity((width * height) as usize);
+  for line in lines {
+    for c in line.chars() {
+      match c {
+        '-' => mask.push(true),
+        '.' => mask.push(false),
+        _ => return None
+      }
+    }
+  }
+  Some((width, height, mask))
+}
+
+// Writes the field image in the format accepted by `parse`, rows are separated by slashes. Passes are skipped.
+// Letters give the move order for games up to 26 moves. In longer games letters split moves into 26 consecutive groups,
+// so the exact move order is appended after ';'.
+pub fn write(field: &Field) -> String {
+  let width = field.width();
+  let height = field.height();
+  let mut cells = vec!['.'; (width * height) as usize];
+  let points = field.points_seq().iter().cloned().filter(|&pos| !field::is_pass(pos)).collect::<Vec<Pos>>();
+  let moves_count = points.len();
+  for (i, &pos) in points.iter().enumerate() {
+    let letter_index = if moves_count <= LETTERS_COUNT { i } else { i * LETTERS_COUNT / moves_count };
+    let letter = char::from_u32('a' as u32 + letter_index as u32).unwrap();
+    let index = (field.to_y(pos) * width + field.to_x(pos)) as usize;
+    cells[index] = if field.get_player(pos) == Player::Black { letter.to_ascii_uppercase() } else { letter };
+  }
+  let image = cells.chunks(width as usize).map(|row| row.iter().cloned().collect::<String>()).collect::<Vec<String>>().join("/");
+  if moves_count <= LETTERS_COUNT {
+    image
+  } else {
+    let order = points.iter().map(|&pos| format!("{},{}", field.to_x(pos), field.to_y(pos))).collect::<Vec<String>>().join(";");
+    format!("{};{}", image, order)
+  }
+}
+
+// Writes the state of every cell, rows are separated by slashes:
+// '.' - empty cell, 'o' / 'O' - red / black point, '#' / '%' - red / black point that bounds a surrounding,
+// '*' - captured point, 'x' / 'X' - empty cell captured by red / black, 'e' / 'E' - red / black empty base,
+// '-' - unplayable cell.
+pub fn write_marks(field: &Field) -> String {
+  let mut rows = Vec::with_capacity(field.height() as usize);
+  for y in 0 .. field.height() {
+    let mut row = String::with_capacity(field.width() as usize);
+    for x in 0 .. field.width() {
+      let pos = field.to_pos(x, y);
+      let black = field.get_player(pos) == Player::Black;
+      row.push(if field.is_put(pos) {
+        if field.is_captured(pos) {
+          '*'
+        } else if field.is_bound(pos) {
+          if black { '%' } else { '#' }
+        } else {
+          if black { 'O' } else { 'o' }
+        }
+      } else if field.is_captured(pos) {
+        if black { 'X' } else { 'x' }
+      } else if field.is_empty_base(pos) {
+        if black { 'E' } else { 'e' }
+      } else if field.is_bad(pos) {
+        '-'
+      } else {
+        '.'
+      });
+    }
+    rows.push(row);
+  }
+  rows.join("/")
+}
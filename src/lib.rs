@@ -0,0 +1,57 @@
+#![allow(dead_code)]
+#![feature(unsafe_no_drop_flag)]
+#![feature(convert)]
+#![feature(plugin)]
+
+#![plugin(clippy)]
+
+extern crate rand;
+
+extern crate time;
+
+#[macro_use]
+extern crate log;
+
+extern crate num_cpus;
+
+extern crate rustc_serialize;
+
+extern crate toml;
+
+extern crate crossbeam;
+
+#[cfg(test)]
+extern crate quickcheck;
+
+pub mod config;
+pub mod player;
+pub mod zobrist;
+mod cell;
+pub mod rules;
+mod snapshot;
+pub mod field;
+mod wave_pruning;
+mod trajectories_pruning;
+pub mod hash_table;
+mod common;
+pub mod uct;
+pub mod heuristic;
+pub mod minimax;
+pub mod bot;
+pub mod sgf;
+pub mod image;
+
+#[cfg(test)]
+mod field_test;
+
+#[cfg(test)]
+mod sgf_test;
+
+#[cfg(test)]
+mod hash_table_test;
+
+#[cfg(test)]
+mod config_test;
+
+#[cfg(test)]
+mod minimax_test;
@@ -1,57 +1,58 @@
-#![allow(dead_code)]
-#![feature(unsafe_no_drop_flag)]
-#![feature(convert)]
 #![feature(plugin)]
 
 #![plugin(clippy)]
 
-extern crate rand;
-
-#[macro_use]
-extern crate log;
-
 extern crate log4rs;
 
-extern crate num_cpus;
-
-extern crate rustc_serialize;
-
-extern crate toml;
-
-extern crate crossbeam;
+extern crate opai_rs;
 
-#[cfg(test)]
-extern crate quickcheck;
-
-mod config;
-mod player;
-mod zobrist;
-mod cell;
-mod field;
-mod wave_pruning;
-mod trajectories_pruning;
-mod common;
-mod uct;
-mod heuristic;
-mod minimax;
-mod bot;
-
-#[cfg(test)]
-mod field_test;
-
-use std::io;
-use std::io::{Write, BufReader, BufRead};
+use std::{io, thread};
+use std::io::{Write, Read, BufReader, BufRead};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::mpsc::{Sender, TryRecvError};
 use std::str::FromStr;
 use std::path::Path;
 use std::fs::File;
 use log4rs::toml::Creator;
-use player::Player;
-use bot::Bot;
+use opai_rs::config;
+use opai_rs::player::Player;
+use opai_rs::field::{Pos, Field, GameResult, CaptureEvent};
+use opai_rs::uct::UctMoveStats;
+use opai_rs::bot::{Bot, BotMove, ScoreEstimation, MoveEvaluation};
+use opai_rs::sgf;
+use opai_rs::image;
+use opai_rs::rules::Rules;
 
 const CONFIG_PATH: &'static str = "config/config.toml";
 
 const LOG_CONFIG_PATH: &'static str = "config/log.toml";
 
+const DEFAULT_ANALYZE_INTERVAL: u32 = 1000;
+
+const ANALYZE_MOVES_COUNT: usize = 10;
+
+fn write_analyze_info<T: Write>(output: &mut T, id: u32, field: &Field, stats: &[UctMoveStats]) {
+  write!(output, "= {0} analyze", id).ok();
+  for move_stats in stats {
+    write!(output, " info move {0} {1} visits {2} winrate {3:.4} pv", field.to_x(move_stats.pos), field.to_y(move_stats.pos), move_stats.visits, move_stats.win_rate).ok();
+    for &pos in &move_stats.pv {
+      write!(output, " {0} {1}", field.to_x(pos), field.to_y(pos)).ok();
+    }
+  }
+  writeln!(output, "").ok();
+  output.flush().ok();
+}
+
+fn write_analyze<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} analyze", id).ok();
+}
+
+fn write_analyze_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} analyze", id).ok();
+}
+
 fn write_author<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} author kurnevsky_evgeny", id).ok();
 }
@@ -60,6 +61,22 @@ fn write_author_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} author", id).ok();
 }
 
+fn write_goto<T: Write>(output: &mut T, id: u32, move_number: usize) {
+  writeln!(output, "= {0} goto {1}", id, move_number).ok();
+}
+
+fn write_goto_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} goto", id).ok();
+}
+
+fn write_ground<T: Write>(output: &mut T, id: u32, player: Player) {
+  writeln!(output, "= {0} ground {1}", id, player.to_bool() as u32).ok();
+}
+
+fn write_ground_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} ground", id).ok();
+}
+
 fn write_init<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} init", id).ok();
 }
@@ -68,30 +85,124 @@ fn write_init_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} init", id).ok();
 }
 
-fn write_gen_move<T: Write>(output: &mut T, id: u32, x: u32, y: u32, player: Player) {
-  writeln!(output, "= {0} gen_move {1} {2} {3}", id, x, y, player.to_bool() as u32).ok();
+fn write_init_shape<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} init_shape", id).ok();
+}
+
+fn write_init_shape_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} init_shape", id).ok();
+}
+
+fn write_estimate_score<T: Write>(output: &mut T, id: u32, width: u32, estimation: &ScoreEstimation) {
+  let ownership = estimation.ownership.chunks(width as usize).map(|row| row.iter().map(|value| format!("{0:.2}", value)).collect::<Vec<String>>().join(",")).collect::<Vec<String>>().join("/");
+  writeln!(output, "= {0} estimate_score {1:.2} {2}", id, estimation.score_lead, ownership).ok();
+}
+
+fn write_estimate_score_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} estimate_score", id).ok();
+}
+
+fn write_game_result<T: Write>(output: &mut T, id: u32, result: GameResult) {
+  match result.winner {
+    Some(player) if result.resignation => writeln!(output, "= {0} game_result {1} resign", id, player.to_bool() as u32).ok(),
+    Some(player) => writeln!(output, "= {0} game_result {1} {2}", id, player.to_bool() as u32, result.score).ok(),
+    None => writeln!(output, "= {0} game_result draw {1}", id, result.score).ok()
+  };
+}
+
+fn write_game_result_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} game_result", id).ok();
+}
+
+// A move is written as its coordinates, or as "pass" or "resign".
+fn bot_move_to_string(bot_move: BotMove) -> String {
+  match bot_move {
+    BotMove::Put(x, y) => format!("{0} {1}", x, y),
+    BotMove::Pass => "pass".to_owned(),
+    BotMove::Resign => "resign".to_owned()
+  }
+}
+
+fn write_gen_move<T: Write>(output: &mut T, id: u32, bot_move: BotMove, player: Player) {
+  writeln!(output, "= {0} gen_move {1} {2}", id, bot_move_to_string(bot_move), player.to_bool() as u32).ok();
 }
 
 fn write_gen_move_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} gen_move", id).ok();
 }
 
-fn write_gen_move_with_complexity<T: Write>(output: &mut T, id: u32, x: u32, y: u32, player: Player) {
-  writeln!(output, "= {0} gen_move_with_complexity {1} {2} {3}", id, x, y, player.to_bool() as u32).ok();
+fn write_gen_move_with_complexity<T: Write>(output: &mut T, id: u32, bot_move: BotMove, player: Player) {
+  writeln!(output, "= {0} gen_move_with_complexity {1} {2}", id, bot_move_to_string(bot_move), player.to_bool() as u32).ok();
 }
 
 fn write_gen_move_with_complexity_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} gen_move_with_complexity", id).ok();
 }
 
-fn write_gen_move_with_time<T: Write>(output: &mut T, id: u32, x: u32, y: u32, player: Player) {
-  writeln!(output, "= {0} gen_move_with_time {1} {2} {3}", id, x, y, player.to_bool() as u32).ok();
+fn write_gen_move_with_time<T: Write>(output: &mut T, id: u32, bot_move: BotMove, player: Player, depth: Option<u32>) {
+  write!(output, "= {0} gen_move_with_time {1} {2}", id, bot_move_to_string(bot_move), player.to_bool() as u32).ok();
+  if let Some(depth) = depth {
+    write!(output, " depth {0}", depth).ok();
+  }
+  writeln!(output, "").ok();
 }
 
 fn write_gen_move_with_time_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} gen_move_with_time", id).ok();
 }
 
+fn write_gen_moves<T: Write>(output: &mut T, id: u32, player: Player, moves: &[(u32, u32, MoveEvaluation)]) {
+  write!(output, "= {0} gen_moves {1}", id, player.to_bool() as u32).ok();
+  for &(x, y, evaluation) in moves {
+    match evaluation {
+      MoveEvaluation::Uct { win_rate, visits } => write!(output, " move {0} {1} winrate {2:.4} visits {3}", x, y, win_rate, visits).ok(),
+      MoveEvaluation::Minimax { value, depth: Some(depth) } => write!(output, " move {0} {1} value {2} depth {3}", x, y, value, depth).ok(),
+      MoveEvaluation::Minimax { value, depth: None } => write!(output, " move {0} {1} value {2}", x, y, value).ok(),
+      MoveEvaluation::Heuristic(estimation) => write!(output, " move {0} {1} heuristic {2}", x, y, estimation).ok()
+    };
+  }
+  writeln!(output, "").ok();
+}
+
+fn write_gen_moves_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} gen_moves", id).ok();
+}
+
+fn write_gen_move_with_clock<T: Write>(output: &mut T, id: u32, bot_move: BotMove, player: Player, depth: Option<u32>) {
+  write!(output, "= {0} gen_move_with_clock {1} {2}", id, bot_move_to_string(bot_move), player.to_bool() as u32).ok();
+  if let Some(depth) = depth {
+    write!(output, " depth {0}", depth).ok();
+  }
+  writeln!(output, "").ok();
+}
+
+fn write_gen_move_with_clock_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} gen_move_with_clock", id).ok();
+}
+
+fn write_positions<T: Write>(output: &mut T, field: &Field, positions: &[Pos]) {
+  for &pos in positions {
+    write!(output, " {0} {1}", field.to_x(pos), field.to_y(pos)).ok();
+  }
+}
+
+fn write_last_captures<T: Write>(output: &mut T, id: u32, field: &Field, captures: &[CaptureEvent]) {
+  write!(output, "= {0} last_captures", id).ok();
+  for capture in captures {
+    write!(output, " capture {0} captured_count {1} freed_count {2} chain", capture.player.to_bool() as u32, capture.captured.len(), capture.freed.len()).ok();
+    write_positions(output, field, &capture.chain);
+    write!(output, " captured").ok();
+    write_positions(output, field, &capture.captured);
+    write!(output, " freed").ok();
+    write_positions(output, field, &capture.freed);
+  }
+  writeln!(output, "").ok();
+}
+
+fn write_last_captures_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} last_captures", id).ok();
+}
+
 fn write_license<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} license AGPLv3+", id).ok();
 }
@@ -101,13 +212,21 @@ fn write_license_error<T: Write>(output: &mut T, id: u32) {
 }
 
 fn write_list_commands<T: Write>(output: &mut T, id: u32) {
-  writeln!(output, "= {0} list_commands gen_move gen_move_with_complexity gen_move_with_time init list_commands name play quit undo version", id).ok();
+  writeln!(output, "= {0} list_commands analyze estimate_score game_result gen_move gen_move_with_clock gen_move_with_complexity gen_move_with_time gen_moves get_option goto ground init init_shape last_captures list_commands loadsgf name pass play play_without_surround quit redo resign savesgf set_option setboard showboard stop undo version", id).ok();
 }
 
 fn write_list_commands_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} list_commands", id).ok();
 }
 
+fn write_loadsgf<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} loadsgf", id).ok();
+}
+
+fn write_loadsgf_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} loadsgf", id).ok();
+}
+
 fn write_name<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} name opai-rust", id).ok();
 }
@@ -116,6 +235,14 @@ fn write_name_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} name", id).ok();
 }
 
+fn write_pass<T: Write>(output: &mut T, id: u32, player: Player) {
+  writeln!(output, "= {0} pass {1}", id, player.to_bool() as u32).ok();
+}
+
+fn write_pass_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} pass", id).ok();
+}
+
 fn write_play<T: Write>(output: &mut T, id: u32, x: u32, y: u32, player: Player) {
   writeln!(output, "= {0} play {1} {2} {3}", id, x, y, player.to_bool() as u32).ok();
 }
@@ -124,6 +251,14 @@ fn write_play_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} play", id).ok();
 }
 
+fn write_play_without_surround<T: Write>(output: &mut T, id: u32, x: u32, y: u32, player: Player) {
+  writeln!(output, "= {0} play_without_surround {1} {2} {3}", id, x, y, player.to_bool() as u32).ok();
+}
+
+fn write_play_without_surround_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} play_without_surround", id).ok();
+}
+
 fn write_quit<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} quit", id).ok();
 }
@@ -132,6 +267,70 @@ fn write_quit_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} quit", id).ok();
 }
 
+fn write_redo<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} redo", id).ok();
+}
+
+fn write_redo_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} redo", id).ok();
+}
+
+fn write_resign<T: Write>(output: &mut T, id: u32, player: Player) {
+  writeln!(output, "= {0} resign {1}", id, player.to_bool() as u32).ok();
+}
+
+fn write_resign_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} resign", id).ok();
+}
+
+fn write_savesgf<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} savesgf", id).ok();
+}
+
+fn write_savesgf_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} savesgf", id).ok();
+}
+
+fn write_setboard<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} setboard", id).ok();
+}
+
+fn write_setboard_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} setboard", id).ok();
+}
+
+fn write_set_option<T: Write>(output: &mut T, id: u32, name: &str, value: &str) {
+  writeln!(output, "= {0} set_option {1} {2}", id, name, value).ok();
+}
+
+fn write_set_option_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} set_option", id).ok();
+}
+
+fn write_get_option<T: Write>(output: &mut T, id: u32, name: &str, value: &str) {
+  writeln!(output, "= {0} get_option {1} {2}", id, name, value).ok();
+}
+
+fn write_get_option_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} get_option", id).ok();
+}
+
+fn write_showboard<T: Write>(output: &mut T, id: u32, field: &Field) {
+  writeln!(output, "= {0} showboard {1} {2} {3} {4}", id, image::write(field), image::write_marks(field), field.captured_count(Player::Red), field.captured_count(Player::Black)).ok();
+}
+
+fn write_showboard_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} showboard", id).ok();
+}
+
+fn write_stop<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "= {0} stop", id).ok();
+}
+
+fn write_stop_error<T: Write>(output: &mut T, id: u32) {
+  writeln!(output, "? {0} stop", id).ok();
+}
+
 fn write_undo<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "= {0} undo", id).ok();
 }
@@ -152,6 +351,50 @@ fn write_error<T: Write>(output: &mut T, id: u32) {
   writeln!(output, "? {0} input_error", id).ok();
 }
 
+// Rules are given as an optional comma separated list of differences from the default rules.
+fn parse_rules(rules_str_option: Option<&str>) -> Option<Rules> {
+  match rules_str_option {
+    Some(rules_str) => Rules::from_str(rules_str).ok(),
+    None => Some(Rules::default())
+  }
+}
+
+fn read_sgf(path: &str) -> Option<sgf::SgfGame> {
+  let mut string = String::new();
+  File::open(path).ok().and_then(|mut file| file.read_to_string(&mut string).ok()).and_then(|_| sgf::parse(string.as_str()).ok())
+}
+
+fn write_sgf(path: &str, bot: &Bot) -> bool {
+  File::create(path).ok().and_then(|mut file| file.write_all(sgf::write(&sgf::from_field(bot.field())).as_bytes()).ok()).is_some()
+}
+
+fn is_interrupting_command(s: &str) -> bool {
+  match s.split(' ').nth(1) {
+    Some("stop") | Some("quit") => true,
+    _ => false
+  }
+}
+
+fn read_input(sender: Sender<String>, should_stop: Arc<AtomicBool>, ponder_should_stop: Arc<AtomicBool>) {
+  let mut input = BufReader::new(io::stdin());
+  loop {
+    let mut s = String::new();
+    match input.read_line(&mut s) {
+      Ok(0) | Err(_) => break,
+      Ok(_) => { }
+    }
+    s.pop();
+    if is_interrupting_command(s.as_str()) {
+      should_stop.store(true, Ordering::Relaxed);
+    }
+    // The flag is set after sending, so the main loop can't clear it and start pondering while the command is waiting.
+    if sender.send(s).is_err() {
+      break;
+    }
+    ponder_should_stop.store(true, Ordering::Relaxed);
+  }
+}
+
 fn main() {
   log4rs::init_file(Path::new(LOG_CONFIG_PATH), Creator::default()).ok();
   config::init();
@@ -160,17 +403,52 @@ fn main() {
   } else if let Some(mut config_file) = File::create(CONFIG_PATH).ok() {
     config::write(&mut config_file);
   }
-  let mut input = BufReader::new(io::stdin());
+  let should_stop = Arc::new(AtomicBool::new(false));
+  let (sender, receiver) = mpsc::channel();
+  let ponder_should_stop = Arc::new(AtomicBool::new(false));
+  let input_should_stop = should_stop.clone();
+  let input_ponder_should_stop = ponder_should_stop.clone();
+  thread::spawn(move || read_input(sender, input_should_stop, input_ponder_should_stop));
   let mut output = io::stdout();
   let mut bot_option = None;
-  let mut s = String::new();
+  let mut ponder = false;
   loop {
-    s.clear();
-    input.read_line(&mut s).ok();
-    s.pop();
+    ponder_should_stop.store(false, Ordering::Relaxed);
+    let s = match receiver.try_recv() {
+      Ok(s) => s,
+      Err(TryRecvError::Empty) => {
+        if let (true, Some(bot)) = (ponder, bot_option.as_mut()) {
+          bot.ponder(&ponder_should_stop);
+        }
+        match receiver.recv() {
+          Ok(s) => s,
+          Err(_) => break
+        }
+      },
+      Err(TryRecvError::Disconnected) => break
+    };
     let mut split = s.split(' ').fuse();
     if let Some(id) = split.next().and_then(|id_str| u32::from_str(id_str).ok()) {
       match split.next() {
+        Some("analyze") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          let interval_option = match split.next() {
+            Some(interval_str) => u32::from_str(interval_str).ok().and_then(|interval| if interval > 0 { Some(interval) } else { None }),
+            None => Some(DEFAULT_ANALYZE_INTERVAL)
+          };
+          if split.next().is_some() {
+            write_analyze_error(&mut output, id);
+          } else if let (Some(player), Some(interval), Some(bot)) = (player_option, interval_option, bot_option.as_mut()) {
+            bot.analyze(player, interval, ANALYZE_MOVES_COUNT, &should_stop, |field, stats| write_analyze_info(&mut output, id, field, stats));
+            write_analyze(&mut output, id);
+          } else {
+            write_analyze_error(&mut output, id);
+          }
+        },
         Some("author") => {
           if split.next().is_some() {
             write_author_error(&mut output, id);
@@ -178,19 +456,132 @@ fn main() {
             write_author(&mut output, id);
           }
         },
+        Some("gen_moves") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          let moves_count_option = split.next().and_then(|moves_count_str| usize::from_str(moves_count_str).ok());
+          if split.next().is_some() {
+            write_gen_moves_error(&mut output, id);
+          } else if let (Some(player), Some(moves_count), Some(bot)) = (player_option, moves_count_option, bot_option.as_mut()) {
+            let moves = bot.best_moves(player, moves_count, &should_stop);
+            write_gen_moves(&mut output, id, player, &moves);
+          } else {
+            write_gen_moves_error(&mut output, id);
+          }
+        },
+        Some("get_option") => {
+          let name_option = split.next();
+          if split.next().is_some() {
+            write_get_option_error(&mut output, id);
+          } else if let Some(name) = name_option {
+            if let Some(value) = config::get_option(name) {
+              write_get_option(&mut output, id, name, value.as_str());
+            } else {
+              write_get_option_error(&mut output, id);
+            }
+          } else {
+            write_get_option_error(&mut output, id);
+          }
+        },
+        Some("goto") => {
+          let move_number_option = split.next().and_then(|move_number_str| usize::from_str(move_number_str).ok());
+          if split.next().is_some() {
+            write_goto_error(&mut output, id);
+          } else if let (Some(move_number), Some(bot)) = (move_number_option, bot_option.as_mut()) {
+            if bot.goto(move_number) {
+              ponder = false;
+              write_goto(&mut output, id, move_number);
+            } else {
+              write_goto_error(&mut output, id);
+            }
+          } else {
+            write_goto_error(&mut output, id);
+          }
+        },
+        Some("ground") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          if split.next().is_some() {
+            write_ground_error(&mut output, id);
+          } else if let (Some(player), Some(bot)) = (player_option, bot_option.as_mut()) {
+            if bot.ground(player) {
+              ponder = false;
+              write_ground(&mut output, id, player);
+            } else {
+              write_ground_error(&mut output, id);
+            }
+          } else {
+            write_ground_error(&mut output, id);
+          }
+        },
         Some("init") => {
           let x_option = split.next().and_then(|x_str| u32::from_str(x_str).ok());
           let y_option = split.next().and_then(|y_str| u32::from_str(y_str).ok());
           let seed_option = split.next().and_then(|seed_str| u64::from_str(seed_str).ok());
+          let rules_option = parse_rules(split.next());
           if split.next().is_some() {
             write_init_error(&mut output, id);
-          } else if let (Some(x), Some(y), Some(seed)) = (x_option, y_option, seed_option) {
-            bot_option = Some(Bot::new(x, y, seed));
-            write_init(&mut output, id);
+          } else if let (Some(x), Some(y), Some(seed), Some(rules)) = (x_option, y_option, seed_option, rules_option) {
+            if rules.supports_size(x, y) {
+              bot_option = Some(Bot::with_rules(x, y, seed, rules));
+              ponder = false;
+              write_init(&mut output, id);
+            } else {
+              write_init_error(&mut output, id);
+            }
           } else {
             write_init_error(&mut output, id);
           }
         },
+        Some("init_shape") => {
+          let mask_option = split.next().and_then(image::parse_mask);
+          let seed_option = split.next().and_then(|seed_str| u64::from_str(seed_str).ok());
+          let rules_option = parse_rules(split.next());
+          if split.next().is_some() {
+            write_init_shape_error(&mut output, id);
+          } else if let (Some((width, height, mask)), Some(seed), Some(rules)) = (mask_option, seed_option, rules_option) {
+            if rules.supports_size(width, height) {
+              bot_option = Some(Bot::with_mask(width, height, seed, rules, mask));
+              ponder = false;
+              write_init_shape(&mut output, id);
+            } else {
+              write_init_shape_error(&mut output, id);
+            }
+          } else {
+            write_init_shape_error(&mut output, id);
+          }
+        },
+        Some("estimate_score") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          let time_option = split.next().and_then(|time_str| u32::from_str(time_str).ok());
+          if split.next().is_some() {
+            write_estimate_score_error(&mut output, id);
+          } else if let (Some(player), Some(time), Some(bot)) = (player_option, time_option, bot_option.as_mut()) {
+            let estimation = bot.estimate_score(player, time, &should_stop);
+            write_estimate_score(&mut output, id, bot.field().width(), &estimation);
+          } else {
+            write_estimate_score_error(&mut output, id);
+          }
+        },
+        Some("game_result") => {
+          if split.next().is_some() {
+            write_game_result_error(&mut output, id);
+          } else if let Some(result) = bot_option.as_ref().and_then(|bot| bot.field().result()) {
+            write_game_result(&mut output, id, result);
+          } else {
+            write_game_result_error(&mut output, id);
+          }
+        },
         Some("gen_move") => {
           let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
             0 => Some(Player::Red),
@@ -200,8 +591,9 @@ fn main() {
           if split.next().is_some() {
             write_gen_move_error(&mut output, id);
           } else if let (Some(player), Some(bot)) = (player_option, bot_option.as_mut()) {
-            if let Some((x, y)) = bot.best_move(player) {
-              write_gen_move(&mut output, id, x, y, player);
+            if let Some(bot_move) = bot.best_move(player, &should_stop) {
+              write_gen_move(&mut output, id, bot_move, player);
+              ponder = config::ponder();
             } else {
               write_gen_move_error(&mut output, id);
             }
@@ -209,6 +601,28 @@ fn main() {
             write_gen_move_error(&mut output, id);
           }
         },
+        Some("gen_move_with_clock") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          let remaining_time_option = split.next().and_then(|remaining_time_str| u32::from_str(remaining_time_str).ok());
+          let increment_option = split.next().and_then(|increment_str| u32::from_str(increment_str).ok());
+          let moves_to_go_option = split.next().and_then(|moves_to_go_str| u32::from_str(moves_to_go_str).ok());
+          if split.next().is_some() {
+            write_gen_move_with_clock_error(&mut output, id);
+          } else if let (Some(player), Some(remaining_time), Some(increment), Some(moves_to_go), Some(bot)) = (player_option, remaining_time_option, increment_option, moves_to_go_option, bot_option.as_mut()) {
+            if let Some(bot_move) = bot.best_move_with_clock(player, remaining_time, increment, moves_to_go, &should_stop) {
+              write_gen_move_with_clock(&mut output, id, bot_move, player, bot.search_depth());
+              ponder = config::ponder();
+            } else {
+              write_gen_move_with_clock_error(&mut output, id);
+            }
+          } else {
+            write_gen_move_with_clock_error(&mut output, id);
+          }
+        },
         Some("gen_move_with_complexity") => {
           let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
             0 => Some(Player::Red),
@@ -219,8 +633,9 @@ fn main() {
           if split.next().is_some() {
             write_gen_move_with_complexity_error(&mut output, id);
           } else if let (Some(player), Some(complexity), Some(bot)) = (player_option, complexity_option, bot_option.as_mut()) {
-            if let Some((x, y)) = bot.best_move_with_complexity(player, complexity) {
-              write_gen_move_with_complexity(&mut output, id, x, y, player);
+            if let Some(bot_move) = bot.best_move_with_complexity(player, complexity, &should_stop) {
+              write_gen_move_with_complexity(&mut output, id, bot_move, player);
+              ponder = config::ponder();
             } else {
               write_gen_move_with_complexity_error(&mut output, id);
             }
@@ -238,8 +653,9 @@ fn main() {
           if split.next().is_some() {
             write_gen_move_with_time_error(&mut output, id);
           } else if let (Some(player), Some(time), Some(bot)) = (player_option, time_option, bot_option.as_mut()) {
-            if let Some((x, y)) = bot.best_move_with_time(player, time) {
-              write_gen_move_with_time(&mut output, id, x, y, player);
+            if let Some(bot_move) = bot.best_move_with_time(player, time, &should_stop) {
+              write_gen_move_with_time(&mut output, id, bot_move, player, bot.search_depth());
+              ponder = config::ponder();
             } else {
               write_gen_move_with_time_error(&mut output, id);
             }
@@ -247,6 +663,15 @@ fn main() {
             write_gen_move_with_time_error(&mut output, id);
           }
         },
+        Some("last_captures") => {
+          if split.next().is_some() {
+            write_last_captures_error(&mut output, id);
+          } else if let Some(bot) = bot_option.as_ref() {
+            write_last_captures(&mut output, id, bot.field(), bot.field().last_captures());
+          } else {
+            write_last_captures_error(&mut output, id);
+          }
+        },
         Some("license") => {
           if split.next().is_some() {
             write_license_error(&mut output, id);
@@ -261,6 +686,25 @@ fn main() {
             write_list_commands(&mut output, id);
           }
         },
+        Some("loadsgf") => {
+          let path_option = split.next();
+          let moves_count_option = match split.next() {
+            Some(moves_count_str) => usize::from_str(moves_count_str).ok().map(Some),
+            None => Some(None)
+          };
+          if split.next().is_some() {
+            write_loadsgf_error(&mut output, id);
+          } else if let (Some(path), Some(moves_count), Some(bot)) = (path_option, moves_count_option, bot_option.as_mut()) {
+            if read_sgf(path).map_or(false, |game| bot.load_sgf(&game, moves_count)) {
+              ponder = false;
+              write_loadsgf(&mut output, id);
+            } else {
+              write_loadsgf_error(&mut output, id);
+            }
+          } else {
+            write_loadsgf_error(&mut output, id);
+          }
+        },
         Some("name") => {
           if split.next().is_some() {
             write_name_error(&mut output, id);
@@ -268,6 +712,25 @@ fn main() {
             write_name(&mut output, id);
           }
         },
+        Some("pass") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          if split.next().is_some() {
+            write_pass_error(&mut output, id);
+          } else if let (Some(player), Some(bot)) = (player_option, bot_option.as_mut()) {
+            if bot.pass(player) {
+              ponder = false;
+              write_pass(&mut output, id, player);
+            } else {
+              write_pass_error(&mut output, id);
+            }
+          } else {
+            write_pass_error(&mut output, id);
+          }
+        },
         Some("play") => {
           let x_option = split.next().and_then(|x_str| u32::from_str(x_str).ok());
           let y_option = split.next().and_then(|y_str| u32::from_str(y_str).ok());
@@ -280,6 +743,7 @@ fn main() {
             write_play_error(&mut output, id);
           } else if let (Some(x), Some(y), Some(player), Some(bot)) = (x_option, y_option, player_option, bot_option.as_mut()) {
             if bot.put_point(x, y, player) {
+              ponder = false;
               write_play(&mut output, id, x, y, player);
             } else {
               write_play_error(&mut output, id);
@@ -288,6 +752,27 @@ fn main() {
             write_play_error(&mut output, id);
           }
         },
+        Some("play_without_surround") => {
+          let x_option = split.next().and_then(|x_str| u32::from_str(x_str).ok());
+          let y_option = split.next().and_then(|y_str| u32::from_str(y_str).ok());
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          if split.next().is_some() {
+            write_play_without_surround_error(&mut output, id);
+          } else if let (Some(x), Some(y), Some(player), Some(bot)) = (x_option, y_option, player_option, bot_option.as_mut()) {
+            if bot.put_point_without_surround(x, y, player) {
+              ponder = false;
+              write_play_without_surround(&mut output, id, x, y, player);
+            } else {
+              write_play_without_surround_error(&mut output, id);
+            }
+          } else {
+            write_play_without_surround_error(&mut output, id);
+          }
+        },
         Some("quit") => {
           if split.next().is_some() {
             write_quit_error(&mut output, id);
@@ -297,11 +782,106 @@ fn main() {
             break;
           }
         },
+        Some("redo") => {
+          if split.next().is_some() {
+            write_redo_error(&mut output, id);
+          } else if let Some(bot) = bot_option.as_mut() {
+            if bot.redo() {
+              ponder = false;
+              write_redo(&mut output, id);
+            } else {
+              write_redo_error(&mut output, id);
+            }
+          } else {
+            write_redo_error(&mut output, id);
+          }
+        },
+        Some("resign") => {
+          let player_option = split.next().and_then(|player_str| u32::from_str(player_str).ok()).and_then(|player_u32| match player_u32 { //TODO: from_number method
+            0 => Some(Player::Red),
+            1 => Some(Player::Black),
+            _ => None
+          });
+          if split.next().is_some() {
+            write_resign_error(&mut output, id);
+          } else if let (Some(player), Some(bot)) = (player_option, bot_option.as_mut()) {
+            if bot.resign(player) {
+              ponder = false;
+              write_resign(&mut output, id, player);
+            } else {
+              write_resign_error(&mut output, id);
+            }
+          } else {
+            write_resign_error(&mut output, id);
+          }
+        },
+        Some("savesgf") => {
+          let path_option = split.next();
+          if split.next().is_some() {
+            write_savesgf_error(&mut output, id);
+          } else if let (Some(path), Some(bot)) = (path_option, bot_option.as_ref()) {
+            if write_sgf(path, bot) {
+              write_savesgf(&mut output, id);
+            } else {
+              write_savesgf_error(&mut output, id);
+            }
+          } else {
+            write_savesgf_error(&mut output, id);
+          }
+        },
+        Some("set_option") => {
+          let name_option = split.next();
+          let value_option = split.next();
+          if split.next().is_some() {
+            write_set_option_error(&mut output, id);
+          } else if let (Some(name), Some(value)) = (name_option, value_option) {
+            if config::set_option(name, value).is_ok() {
+              write_set_option(&mut output, id, name, value);
+            } else {
+              write_set_option_error(&mut output, id);
+            }
+          } else {
+            write_set_option_error(&mut output, id);
+          }
+        },
+        Some("setboard") => {
+          let image_option = split.next().and_then(|image_str| image::parse(image_str));
+          if split.next().is_some() {
+            write_setboard_error(&mut output, id);
+          } else if let (Some((width, height, moves)), Some(bot)) = (image_option, bot_option.as_mut()) {
+            if bot.set_moves(width, height, &moves) {
+              ponder = false;
+              write_setboard(&mut output, id);
+            } else {
+              write_setboard_error(&mut output, id);
+            }
+          } else {
+            write_setboard_error(&mut output, id);
+          }
+        },
+        Some("showboard") => {
+          if split.next().is_some() {
+            write_showboard_error(&mut output, id);
+          } else if let Some(bot) = bot_option.as_ref() {
+            write_showboard(&mut output, id, bot.field());
+          } else {
+            write_showboard_error(&mut output, id);
+          }
+        },
+        Some("stop") => {
+          if split.next().is_some() {
+            write_stop_error(&mut output, id);
+          } else {
+            should_stop.store(false, Ordering::Relaxed);
+            write_stop(&mut output, id);
+          }
+        },
         Some("undo") => {
           if split.next().is_some() {
             write_undo_error(&mut output, id);
           } else if let Some(bot) = bot_option.as_mut() {
             if bot.undo() {
+              ponder = false;
               write_undo(&mut output, id);
             } else {
               write_undo_error(&mut output, id);
@@ -1,29 +1,137 @@
-use std::{iter, thread};
-use std::sync::atomic::{AtomicIsize, AtomicUsize, AtomicBool, Ordering};
+use std::iter;
+use std::sync::atomic::{AtomicBool, Ordering};
 use rand::{Rng, XorShiftRng};
 use crossbeam;
-use crossbeam::sync::MsQueue;
+use time;
 use config;
+use config::MinimaxType;
 use player::Player;
 use field::{Pos, Field};
 use trajectories_pruning::TrajectoriesPruning;
+use hash_table::{HashTable, HashEntry, HashBound};
 use common;
 
 const MINIMAX_STR: &'static str = "minimax";
 
-fn alpha_beta<T: Rng>(field: &mut Field, depth: u32, last_pos: Pos, player: Player, trajectories_pruning: &TrajectoriesPruning, mut alpha: i32, beta: i32, empty_board: &mut Vec<u32>, rng: &mut T, should_stop: &AtomicBool) -> i32 {
+// Settings of a search. They are read from the config once when the search starts.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MinimaxOptions {
+  pub minimax_type: MinimaxType,
+  pub threads_count: usize,
+  pub quiescence_depth: u32
+}
+
+impl MinimaxOptions {
+  pub fn from_config() -> MinimaxOptions {
+    MinimaxOptions {
+      minimax_type: config::minimax_type(),
+      threads_count: config::threads_count(),
+      quiescence_depth: config::minimax_quiescence_depth()
+    }
+  }
+}
+
+// Bound of a value found by a search with the window (alpha, beta). Values not greater than alpha mean only that the real value is not greater.
+fn hash_bound(value: i32, alpha: i32, beta: i32) -> HashBound {
+  if value <= alpha {
+    HashBound::Upper
+  } else if value >= beta {
+    HashBound::Lower
+  } else {
+    HashBound::Exact
+  }
+}
+
+// Depth of trajectories pruning that finds surroundings completed by one move of either player.
+const QUIESCENCE_TRAJECTORIES_DEPTH: u32 = 2;
+
+// Extends the search after the depth limit with moves that complete or prevent surroundings, so that a surrounding
+// right behind the horizon is taken into account. The player may also stop the sequence and keep the current score.
+// The root of quiescence search finds surroundings on the whole field, deeper nodes take them from the parent node
+// and search new ones only around the last move.
+fn quiescence<T: Rng>(field: &mut Field, depth: u32, last_pos: Pos, player: Player, last_trajectories_pruning: Option<&TrajectoriesPruning>, mut alpha: i32, beta: i32, empty_board: &mut Vec<u32>, rng: &mut T, should_stop: &AtomicBool) -> i32 {
+  if field.is_game_over() {
+    return field.final_score(player);
+  }
+  let score = field.score(player);
+  if depth == 0 || score >= beta || should_stop.load(Ordering::Relaxed) {
+    return score;
+  }
+  if score > alpha {
+    alpha = score;
+  }
+  let enemy = player.next();
+  let trajectories_pruning = match last_trajectories_pruning {
+    Some(last) => TrajectoriesPruning::quiescence(field, player, empty_board, rng, last, last_pos, should_stop),
+    None => TrajectoriesPruning::new(field, player, QUIESCENCE_TRAJECTORIES_DEPTH, empty_board, rng, should_stop)
+  };
+  for &pos in trajectories_pruning.moves() {
+    if should_stop.load(Ordering::Relaxed) {
+      break;
+    }
+    field.put_point(pos, player);
+    if common::is_penult_move_stuped(field) {
+      field.undo();
+      return i32::max_value();
+    }
+    if common::is_last_move_stupid(field, pos, player) {
+      field.undo();
+      continue;
+    }
+    let cur_estimation = -quiescence(field, depth - 1, pos, enemy, Some(&trajectories_pruning), -beta, -alpha, empty_board, rng, should_stop);
+    field.undo();
+    if cur_estimation > alpha {
+      alpha = cur_estimation;
+      if alpha >= beta {
+        break;
+      }
+    }
+  }
+  alpha
+}
+
+// Stupid moves are estimated with sentinel values, which depend on the previous move rather than on the position, so values
+// equal to them are never stored in the hash table.
+#[inline]
+fn is_sentinel(value: i32) -> bool {
+  value == i32::max_value() || value == i32::min_value() + 1
+}
+
+// Fail-soft principal variation search: a value outside of the window (alpha, beta) is a bound that may be tighter than the window,
+// which lets MTD(f) narrow the bounds faster.
+fn alpha_beta<T: Rng>(field: &mut Field, depth: u32, last_pos: Pos, player: Player, trajectories_pruning: &TrajectoriesPruning, mut alpha: i32, beta: i32, quiescence_depth: u32, hash_table: &HashTable, empty_board: &mut Vec<u32>, rng: &mut T, should_stop: &AtomicBool) -> i32 {
   let enemy = player.next();
   if common::is_last_move_stupid(field, last_pos, enemy) {
     return i32::max_value();
   }
+  if field.is_game_over() {
+    return field.final_score(player);
+  }
   if depth == 0 {
-    return field.score(player);
+    return quiescence(field, quiescence_depth, last_pos, player, None, alpha, beta, empty_board, rng, should_stop);
   }
   let moves = trajectories_pruning.moves();
   if moves.is_empty() {
     return field.score(player);
   }
-  for &pos in moves {
+  // Entries are keyed by the position and the player only, while searched moves depend on trajectories built along the path,
+  // so a transposition reached by another path may reuse a value calculated over a different set of moves. It's an accepted approximation.
+  let hash_entry = hash_table.get(field.hash(), player);
+  if let Some(entry) = hash_entry {
+    if entry.depth >= depth {
+      match entry.bound {
+        HashBound::Exact => return entry.value,
+        HashBound::Lower if entry.value >= beta => return entry.value,
+        HashBound::Upper if entry.value <= alpha => return entry.value,
+        _ => { }
+      }
+    }
+  }
+  let hash_move = hash_entry.and_then(|entry| entry.best_move).and_then(|pos| if moves.contains(&pos) { Some(pos) } else { None });
+  let start_alpha = alpha;
+  let mut best_estimation = i32::min_value() + 1;
+  let mut best_move = None;
+  for pos in hash_move.into_iter().chain(moves.iter().cloned().filter(|&pos| Some(pos) != hash_move)) {
     if should_stop.load(Ordering::Relaxed) {
       break;
     }
@@ -33,23 +141,108 @@ fn alpha_beta<T: Rng>(field: &mut Field, depth: u32, last_pos: Pos, player: Play
       return i32::max_value();
     }
     let next_trajectories_pruning = TrajectoriesPruning::from_last(field, enemy, depth - 1, empty_board, rng, trajectories_pruning, pos, should_stop);
-    let mut cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -alpha - 1, -alpha, empty_board, rng, should_stop);
+    let mut cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -alpha - 1, -alpha, quiescence_depth, hash_table, empty_board, rng, should_stop);
     if cur_estimation > alpha && cur_estimation < beta {
-      cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -beta, -cur_estimation, empty_board, rng, should_stop);
+      cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -beta, -cur_estimation, quiescence_depth, hash_table, empty_board, rng, should_stop);
     }
     field.undo();
-    if cur_estimation > alpha {
-      alpha = cur_estimation;
-      if alpha >= beta {
-        break;
+    if cur_estimation > best_estimation {
+      best_estimation = cur_estimation;
+      if cur_estimation > alpha {
+        alpha = cur_estimation;
+        best_move = Some(pos);
+        if alpha >= beta {
+          break;
+        }
       }
     }
   }
-  alpha
+  // If all moves are stupid or the search is stopped before any move, only the bound given by the window is known.
+  let estimation = if best_estimation == i32::min_value() + 1 { start_alpha } else { best_estimation };
+  if !should_stop.load(Ordering::Relaxed) && !is_sentinel(estimation) {
+    hash_table.put(field.hash(), player, HashEntry {
+      depth: depth,
+      bound: hash_bound(estimation, start_alpha, beta),
+      value: estimation,
+      best_move: best_move.or(hash_move)
+    });
+  }
+  estimation
+}
+
+// Searches root moves in the given order with a principal variation search. Returns the estimation, the best move and the number of
+// completed root moves. A move whose search was interrupted is not taken into account, so if the best move is found, it is either
+// the first move or a move that is proven to be better than it.
+fn alpha_beta_root<T: Rng>(field: &mut Field, player: Player, depth: u32, mut alpha: i32, beta: i32, moves: &[Pos], trajectories_pruning: &TrajectoriesPruning, quiescence_depth: u32, hash_table: &HashTable, empty_board: &mut Vec<u32>, rng: &mut T, should_stop: &AtomicBool) -> (i32, Option<Pos>, usize) {
+  let enemy = player.next();
+  let start_alpha = alpha;
+  let mut best_estimation = i32::min_value() + 1;
+  let mut best_move = None;
+  let mut completed_moves_count = 0;
+  for &pos in moves {
+    if should_stop.load(Ordering::Relaxed) {
+      debug!(target: MINIMAX_STR, "Time-out!");
+      break;
+    }
+    field.put_point(pos, player);
+    let next_trajectories_pruning = TrajectoriesPruning::from_last(field, enemy, depth - 1, empty_board, rng, trajectories_pruning, pos, should_stop);
+    let mut cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -alpha - 1, -alpha, quiescence_depth, hash_table, empty_board, rng, should_stop);
+    if cur_estimation > alpha && cur_estimation < beta && !should_stop.load(Ordering::Relaxed) {
+      cur_estimation = -alpha_beta(field, depth - 1, pos, enemy, &next_trajectories_pruning, -beta, -cur_estimation, quiescence_depth, hash_table, empty_board, rng, should_stop);
+    }
+    field.undo();
+    if should_stop.load(Ordering::Relaxed) {
+      debug!(target: MINIMAX_STR, "Time-out! Search of move ({}, {}) is not completed.", field.to_x(pos), field.to_y(pos));
+      break;
+    }
+    completed_moves_count += 1;
+    debug!(target: MINIMAX_STR, "{} for move ({}, {}) is {}.", if cur_estimation > alpha { if cur_estimation < beta { "Estimation" } else { "Lower bound of estimation" } } else { "Upper bound of estimation" }, field.to_x(pos), field.to_y(pos), cur_estimation);
+    if cur_estimation > best_estimation {
+      best_estimation = cur_estimation;
+      if cur_estimation > alpha {
+        alpha = cur_estimation;
+        best_move = Some(pos);
+        if alpha >= beta {
+          break;
+        }
+      }
+    }
+  }
+  (if best_estimation == i32::min_value() + 1 { start_alpha } else { best_estimation }, best_move, completed_moves_count)
+}
+
+// Lazy SMP: helper threads search all root moves in random order sharing the hash table with the main search, so they fill
+// the hash table with results the main search needs later. Every second helper searches one ply deeper, so its entries are
+// deep enough for cutoffs in the main search and threads diverge more. Only the result of the main search is used,
+// helpers are stopped as soon as it finishes.
+fn lazy_smp<T: Rng, R, F: FnOnce(&mut Field, &mut Vec<u32>, &mut T) -> R>(field: &Field, player: Player, depth: u32, alpha: i32, beta: i32, root_moves: &[Pos], trajectories_pruning: &TrajectoriesPruning, options: MinimaxOptions, hash_table: &HashTable, rng: &mut T, main_search: F) -> R {
+  let helpers_should_stop = AtomicBool::new(false);
+  crossbeam::scope(|scope| {
+    for i in 1 .. options.threads_count {
+      let helper = (rng.gen::<XorShiftRng>(), depth + (i % 2) as u32);
+      scope.spawn(|| {
+        let (mut local_rng, helper_depth) = helper;
+        let mut local_field = field.clone();
+        let mut local_empty_board = iter::repeat(0u32).take(field.length()).collect();
+        let mut local_moves = root_moves.to_vec();
+        local_rng.shuffle(&mut local_moves);
+        alpha_beta_root(&mut local_field, player, helper_depth, alpha, beta, &local_moves, trajectories_pruning, options.quiescence_depth, hash_table, &mut local_empty_board, &mut local_rng, &helpers_should_stop);
+      });
+    }
+    let mut main_field = field.clone();
+    let mut empty_board = iter::repeat(0u32).take(field.length()).collect();
+    let result = main_search(&mut main_field, &mut empty_board, rng);
+    helpers_should_stop.store(true, Ordering::Relaxed);
+    result
+  })
 }
 
-fn alpha_beta_parallel<T: Rng>(field: &mut Field, player: Player, depth: u32, alpha: i32, beta: i32, trajectories_pruning: &TrajectoriesPruning, rng: &mut T, best_move: &mut Option<Pos>, should_stop: &AtomicBool) -> i32 {
+fn alpha_beta_parallel<T: Rng>(field: &mut Field, player: Player, depth: u32, alpha: i32, beta: i32, trajectories_pruning: &TrajectoriesPruning, options: MinimaxOptions, hash_table: &HashTable, rng: &mut T, best_move: &mut Option<Pos>, should_stop: &AtomicBool) -> i32 {
   info!(target: MINIMAX_STR, "Starting parellel alpha beta with depth {}, player {} and beta {}.", depth, player, beta);
+  if field.is_game_over() {
+    *best_move = None;
+    return field.final_score(player);
+  }
   if depth == 0 || should_stop.load(Ordering::Relaxed) {
     *best_move = None;
     return field.score(player);
@@ -60,98 +253,107 @@ fn alpha_beta_parallel<T: Rng>(field: &mut Field, player: Player, depth: u32, al
     *best_move = None;
     return field.score(player);
   }
-  let queue = MsQueue::new();
-  if let Some(best_pos) = *best_move {
-    queue.push(best_pos);
-    for &pos in moves.iter().filter(|&&pos| pos != best_pos) {
-      queue.push(pos);
-    }
-  } else {
-    for &pos in moves.iter() {
-      queue.push(pos);
-    }
+  let first_move = best_move.or_else(|| hash_table.get(field.hash(), player).and_then(|entry| entry.best_move)).and_then(|pos| if moves.contains(&pos) { Some(pos) } else { None });
+  let mut root_moves = Vec::with_capacity(moves.len());
+  if let Some(best_pos) = first_move {
+    root_moves.push(best_pos);
   }
-  let threads_count = config::threads_count();
-  let atomic_alpha = AtomicIsize::new(alpha as isize);
-  let atomic_best_move = AtomicUsize::new(0);
-  crossbeam::scope(|scope| {
-    for _ in 0 .. threads_count {
-      let xor_shift_rng = rng.gen::<XorShiftRng>();
-      scope.spawn(|| {
-        let mut local_field = field.clone();
-        let mut local_rng = xor_shift_rng;
-        let mut local_empty_board = iter::repeat(0u32).take(field.length()).collect();
-        let enemy = player.next();
-        while let Some(pos) = queue.pop() {
-          if should_stop.load(Ordering::Relaxed) {
-            debug!(target: MINIMAX_STR, "Time-out!");
-            break;
-          }
-          local_field.put_point(pos, player);
-          let next_trajectories_pruning = TrajectoriesPruning::from_last(&mut local_field, enemy, depth - 1, &mut local_empty_board, &mut local_rng, &trajectories_pruning, pos, should_stop);
-          if should_stop.load(Ordering::Relaxed) {
-            debug!(target: MINIMAX_STR, "Time-out!");
-            break;
-          }
-          let cur_alpha = atomic_alpha.load(Ordering::SeqCst) as i32;
-          if cur_alpha >= beta {
-            break;
-          }
-          let mut cur_estimation = -alpha_beta(&mut local_field, depth - 1, pos, enemy, &next_trajectories_pruning, -cur_alpha - 1, -cur_alpha, &mut local_empty_board, &mut local_rng, should_stop);
-          if cur_estimation > cur_alpha {
-            if !should_stop.load(Ordering::Relaxed) {
-              cur_estimation = -alpha_beta(&mut local_field, depth - 1, pos, enemy, &next_trajectories_pruning, -beta, -cur_estimation, &mut local_empty_board, &mut local_rng, should_stop);
-            } else {
-              debug!(target: MINIMAX_STR, "Time-out! Next estimation ma be approximated.");
-            }
-          }
-          local_field.undo();
-          loop {
-            let last_pos = atomic_best_move.load(Ordering::SeqCst);
-            let last_alpha = atomic_alpha.load(Ordering::SeqCst);
-            if cur_estimation > last_alpha as i32 {
-              if atomic_alpha.compare_and_swap(last_alpha, cur_estimation as isize, Ordering::SeqCst) == last_alpha && atomic_best_move.compare_and_swap(last_pos, pos, Ordering::SeqCst) == last_pos {
-                debug!(target: MINIMAX_STR, "{} for move ({}, {}) is {}.", if cur_estimation < beta { "Estimation" } else { "Lower bound of estimation" }, field.to_x(pos), field.to_y(pos), cur_estimation);
-                break;
-              }
-            } else {
-              debug!(target: MINIMAX_STR, "{} for move ({}, {}) is {}.", if cur_estimation > cur_alpha { if cur_estimation < beta { "Estimation" } else { "Lower bound of estimation" } } else { "Upper bound of estimation" }, field.to_x(pos), field.to_y(pos), cur_estimation);
-              break;
-            }
-          }
-        }
-      });
-    }
+  for &pos in moves.iter().filter(|&&pos| Some(pos) != first_move) {
+    root_moves.push(pos);
+  }
+  let start_time = time::precise_time_ns();
+  let (cur_alpha, cur_best_move, completed_moves_count) = lazy_smp(field, player, depth, alpha, beta, &root_moves, trajectories_pruning, options, hash_table, rng, |main_field, empty_board, rng| {
+    alpha_beta_root(main_field, player, depth, alpha, beta, &root_moves, trajectories_pruning, options.quiescence_depth, hash_table, empty_board, rng, should_stop)
   });
-  let result = atomic_best_move.load(Ordering::SeqCst);
-  if result != 0 {
-    info!(target: MINIMAX_STR, "Best move is ({}, {}).", field.to_x(result), field.to_y(result));
-    *best_move = Some(result);
+  info!(target: MINIMAX_STR, "Search with {} threads took {} ms.", options.threads_count, (time::precise_time_ns() - start_time) / 1000000);
+  if should_stop.load(Ordering::Relaxed) {
+    info!(target: MINIMAX_STR, "Search was interrupted, {} of {} root moves are completed.", completed_moves_count, root_moves.len());
+  }
+  if let Some(pos) = cur_best_move {
+    info!(target: MINIMAX_STR, "Best move is ({}, {}).", field.to_x(pos), field.to_y(pos));
   } else {
     info!(target: MINIMAX_STR, "Best move is not found.");
-    *best_move = None;
   }
-  let cur_alpha = atomic_alpha.load(Ordering::SeqCst);
+  *best_move = cur_best_move;
   info!(target: MINIMAX_STR, "Estimation is {}.", cur_alpha);
-  cur_alpha as i32
+  if !should_stop.load(Ordering::Relaxed) && !is_sentinel(cur_alpha) {
+    hash_table.put(field.hash(), player, HashEntry {
+      depth: depth,
+      bound: hash_bound(cur_alpha, alpha, beta),
+      value: cur_alpha,
+      best_move: cur_best_move
+    });
+  }
+  cur_alpha
+}
+
+// MTD(f): null window searches narrow the bounds of the estimation starting from a guess, which is taken from the hash table,
+// so the result of the previous iteration of deepening is used. The best move is taken from the last fail high search,
+// it's None if no search failed high, in particular if the first search was interrupted.
+fn mtdf<T: Rng>(field: &mut Field, player: Player, depth: u32, trajectories_pruning: &TrajectoriesPruning, options: MinimaxOptions, hash_table: &HashTable, rng: &mut T, best_move: &mut Option<Pos>, should_stop: &AtomicBool) -> i32 {
+  let mut lower_bound = i32::min_value() + 1;
+  let mut upper_bound = i32::max_value();
+  let mut estimation = hash_table.get(field.hash(), player).map_or(field.score(player), |entry| entry.value);
+  let mut first_move = *best_move;
+  *best_move = None;
+  while lower_bound < upper_bound && !should_stop.load(Ordering::Relaxed) {
+    let window = if estimation == lower_bound { estimation + 1 } else { estimation };
+    let mut cur_best_move = first_move;
+    info!(target: MINIMAX_STR, "MTD(f) search with bounds {} - {} and window {}.", lower_bound, upper_bound, window);
+    estimation = alpha_beta_parallel(field, player, depth, window - 1, window, trajectories_pruning, options, hash_table, rng, &mut cur_best_move, should_stop);
+    if should_stop.load(Ordering::Relaxed) {
+      break;
+    }
+    if estimation < window {
+      upper_bound = estimation;
+    } else {
+      lower_bound = estimation;
+      *best_move = cur_best_move;
+      first_move = cur_best_move;
+    }
+  }
+  estimation
+}
+
+// Full window search of the root with the given algorithm.
+fn search_root<T: Rng>(field: &mut Field, player: Player, depth: u32, trajectories_pruning: &TrajectoriesPruning, options: MinimaxOptions, hash_table: &HashTable, rng: &mut T, best_move: &mut Option<Pos>, should_stop: &AtomicBool) -> i32 {
+  match options.minimax_type {
+    MinimaxType::NegaScout => alpha_beta_parallel(field, player, depth, i32::min_value() + 1, i32::max_value(), trajectories_pruning, options, hash_table, rng, best_move, should_stop),
+    MinimaxType::MTDF => mtdf(field, player, depth, trajectories_pruning, options, hash_table, rng, best_move, should_stop)
+  }
 }
 
-pub fn minimax<T: Rng>(field: &mut Field, player: Player, rng: &mut T, depth: u32) -> Option<Pos> {
+// Searches the position with the given depth and options. Returns the estimation and the best move.
+pub fn minimax_estimation<T: Rng>(field: &mut Field, player: Player, hash_table: &HashTable, rng: &mut T, depth: u32, options: MinimaxOptions, should_stop: &AtomicBool) -> (i32, Option<Pos>) {
+  if depth == 0 || field.is_game_over() {
+    return (field.final_score(player), None);
+  }
+  let mut empty_board = iter::repeat(0u32).take(field.length()).collect();
+  let trajectories_pruning = TrajectoriesPruning::new(field, player, depth, &mut empty_board, rng, should_stop);
+  let mut best_move = None;
+  let estimation = search_root(field, player, depth, &trajectories_pruning, options, hash_table, rng, &mut best_move, should_stop);
+  (estimation, best_move)
+}
+
+pub fn minimax<T: Rng>(field: &mut Field, player: Player, hash_table: &HashTable, rng: &mut T, depth: u32, options: MinimaxOptions, should_stop: &AtomicBool) -> Option<Pos> {
   info!(target: MINIMAX_STR, "Starting minimax with depth {} and player {}.", depth, player);
-  if depth == 0 {
+  if depth == 0 || field.is_game_over() {
     return None;
   }
-  let should_stop = AtomicBool::new(false);
   let mut empty_board = iter::repeat(0u32).take(field.length()).collect();
-  let trajectories_pruning = TrajectoriesPruning::new(field, player, depth, &mut empty_board, rng, &should_stop);
+  let trajectories_pruning = TrajectoriesPruning::new(field, player, depth, &mut empty_board, rng, should_stop);
   let mut best_move = None;
   info!(target: MINIMAX_STR, "Calculating of our estimation. Player is {}", player);
-  let estimation = alpha_beta_parallel(field, player, depth, i32::min_value() + 1, i32::max_value(), &trajectories_pruning, rng, &mut best_move, &should_stop);
+  let estimation = search_root(field, player, depth, &trajectories_pruning, options, hash_table, rng, &mut best_move, should_stop);
+  if should_stop.load(Ordering::Relaxed) {
+    info!(target: MINIMAX_STR, "Search was interrupted. So the best move is {:?}.", best_move.map(|pos| (field.to_x(pos), field.to_y(pos))));
+    return best_move;
+  }
   let enemy = player.next();
   let mut enemy_best_move = best_move;
-  let enemy_trajectories_pruning = TrajectoriesPruning::dec_and_swap_exists(&field, depth - 1, &mut empty_board, rng, &trajectories_pruning, &should_stop);
+  let enemy_trajectories_pruning = TrajectoriesPruning::dec_and_swap_exists(&field, depth - 1, &mut empty_board, rng, &trajectories_pruning, should_stop);
   info!(target: MINIMAX_STR, "Calculating of enemy estimation with upper bound {}. Player is {}", -estimation + 1, enemy);
-  if -alpha_beta_parallel(field, enemy, depth - 1, -estimation, -estimation + 1, &enemy_trajectories_pruning, rng, &mut enemy_best_move, &should_stop) < estimation {
+  if -alpha_beta_parallel(field, enemy, depth - 1, -estimation, -estimation + 1, &enemy_trajectories_pruning, options, hash_table, rng, &mut enemy_best_move, should_stop) < estimation {
     info!(target: MINIMAX_STR,  "Estimation is greater than enemy estimation. So the best move is {:?}, estimation is {}.", best_move.map(|pos| (field.to_x(pos), field.to_y(pos))), estimation);
     best_move
   } else {
@@ -160,40 +362,105 @@ pub fn minimax<T: Rng>(field: &mut Field, player: Player, rng: &mut T, depth: u3
   }
 }
 
-pub fn minimax_with_time<T: Rng>(field: &mut Field, player: Player, rng: &mut T, time: u32) -> Option<Pos> {
+// Searches exact estimations of the best moves_count moves. Every root move is searched with the window bounded below by
+// the estimation of the worst move among already found best moves, so estimations of the found moves are exact.
+// Also returns the depth if the search is completed. An interrupted search may miss better moves, so it has no depth.
+pub fn minimax_multi_pv<T: Rng>(field: &mut Field, player: Player, hash_table: &HashTable, rng: &mut T, depth: u32, moves_count: usize, options: MinimaxOptions, should_stop: &AtomicBool) -> (Vec<(Pos, i32)>, Option<u32>) {
+  info!(target: MINIMAX_STR, "Starting multi-pv minimax with depth {}, player {} and {} moves.", depth, player, moves_count);
+  if depth == 0 || moves_count == 0 || field.is_game_over() {
+    return (Vec::new(), None);
+  }
+  let mut empty_board = iter::repeat(0u32).take(field.length()).collect();
+  let trajectories_pruning = TrajectoriesPruning::new(field, player, depth, &mut empty_board, rng, should_stop);
+  let root_moves = trajectories_pruning.moves().clone();
+  let enemy = player.next();
+  let result = lazy_smp(field, player, depth, i32::min_value() + 1, i32::max_value(), &root_moves, &trajectories_pruning, options, hash_table, rng, |main_field, empty_board, rng| {
+    let mut best_moves = Vec::with_capacity(moves_count + 1);
+    for &pos in &root_moves {
+      if should_stop.load(Ordering::Relaxed) {
+        break;
+      }
+      main_field.put_point(pos, player);
+      let next_trajectories_pruning = TrajectoriesPruning::from_last(main_field, enemy, depth - 1, empty_board, rng, &trajectories_pruning, pos, should_stop);
+      let bound = if best_moves.len() < moves_count { i32::min_value() + 1 } else { best_moves.last().map_or(i32::min_value() + 1, |&(_, estimation)| estimation) };
+      let estimation = -alpha_beta(main_field, depth - 1, pos, enemy, &next_trajectories_pruning, -i32::max_value(), -bound, options.quiescence_depth, hash_table, empty_board, rng, should_stop);
+      main_field.undo();
+      if should_stop.load(Ordering::Relaxed) {
+        break;
+      }
+      if estimation > bound || bound == i32::min_value() + 1 {
+        debug!(target: MINIMAX_STR, "Estimation for move ({}, {}) is {}.", main_field.to_x(pos), main_field.to_y(pos), estimation);
+        let index = best_moves.iter().position(|&(_, best_estimation)| estimation > best_estimation).unwrap_or(best_moves.len());
+        best_moves.insert(index, (pos, estimation));
+        best_moves.truncate(moves_count);
+      }
+    }
+    best_moves
+  });
+  let completed_depth = if should_stop.load(Ordering::Relaxed) { None } else { Some(depth) };
+  info!(target: MINIMAX_STR, "Best moves are {:?}, completed depth is {:?}.", result.iter().map(|&(pos, estimation)| (field.to_x(pos), field.to_y(pos), estimation)).collect::<Vec<(u32, u32, i32)>>(), completed_depth);
+  (result, completed_depth)
+}
+
+pub fn minimax_with_time<T: Rng>(field: &mut Field, player: Player, hash_table: &HashTable, rng: &mut T, time: u32, options: MinimaxOptions, external_should_stop: &AtomicBool) -> (Option<Pos>, u32) {
+  minimax_with_stability(field, player, hash_table, rng, time, time, options, external_should_stop)
+}
+
+// Iterative deepening that takes at least min_time and at most max_time. After min_time it's stopped as soon as
+// an iteration of deepening gives the same best move as the previous one. Also returns the depth of the last completed iteration.
+pub fn minimax_with_stability<T: Rng>(field: &mut Field, player: Player, hash_table: &HashTable, rng: &mut T, min_time: u32, max_time: u32, options: MinimaxOptions, external_should_stop: &AtomicBool) -> (Option<Pos>, u32) {
+  if field.is_game_over() {
+    return (None, 0);
+  }
   let should_stop = AtomicBool::new(false);
+  let start_time = time::precise_time_ns();
   crossbeam::scope(|scope| {
-    scope.spawn(|| {
-      thread::sleep_ms(time);
-      should_stop.store(true, Ordering::Relaxed);
-    });
+    scope.spawn(|| common::wait_for_stop(max_time, external_should_stop, &should_stop));
     let enemy = player.next();
     let mut depth = 1;
+    let mut reached_depth = 0;
     let mut best_move = None;
+    let mut best_estimation = i32::min_value();
     let mut cur_best_move = None;
     let mut enemy_best_move = None;
     let mut empty_board = iter::repeat(0u32).take(field.length()).collect();
     let mut trajectories_pruning = TrajectoriesPruning::new(field, player, depth, &mut empty_board, rng, &should_stop);
     while !should_stop.load(Ordering::Relaxed) {
-      let estimation = alpha_beta_parallel(field, player, depth, i32::min_value() + 1, i32::max_value(), &trajectories_pruning, rng, &mut cur_best_move, &should_stop);
-      if should_stop.load(Ordering::Relaxed) { //TODO: use calculated move.
-        break;
-      }
-      let enemy_trajectories_pruning = TrajectoriesPruning::dec_and_swap_exists(&field, depth - 1, &mut empty_board, rng, &trajectories_pruning, &should_stop);
-      if should_stop.load(Ordering::Relaxed) {
-        break;
-      }
-      best_move = if -alpha_beta_parallel(field, enemy, depth - 1, -estimation, -estimation + 1, &enemy_trajectories_pruning, rng, &mut enemy_best_move, &should_stop) < estimation || should_stop.load(Ordering::Relaxed) {
-        cur_best_move
-      } else {
+      let estimation = search_root(field, player, depth, &trajectories_pruning, options, hash_table, rng, &mut cur_best_move, &should_stop);
+      let enemy_estimation = if should_stop.load(Ordering::Relaxed) {
         None
+      } else {
+        let enemy_trajectories_pruning = TrajectoriesPruning::dec_and_swap_exists(&field, depth - 1, &mut empty_board, rng, &trajectories_pruning, &should_stop);
+        let enemy_estimation = -alpha_beta_parallel(field, enemy, depth - 1, -estimation, -estimation + 1, &enemy_trajectories_pruning, options, hash_table, rng, &mut enemy_best_move, &should_stop);
+        if should_stop.load(Ordering::Relaxed) { None } else { Some(enemy_estimation) }
       };
-      if should_stop.load(Ordering::Relaxed) {
+      let enemy_estimation = match enemy_estimation {
+        Some(enemy_estimation) => enemy_estimation,
+        None => {
+          // The previous best move is searched first and interrupted searches are not taken into account, so a move found by
+          // the interrupted iteration is at least as good as the previous best move at this depth. But it isn't compared with
+          // the enemy's estimation, so it only replaces a move that passed this check at the previous depth, and only if it's not worse.
+          if cur_best_move.is_some() && best_move.is_some() && estimation >= best_estimation {
+            info!(target: MINIMAX_STR, "Using the best move {:?} of the interrupted iteration with depth {}.", cur_best_move.map(|pos| (field.to_x(pos), field.to_y(pos))), depth);
+            best_move = cur_best_move;
+          }
+          break;
+        }
+      };
+      let last_best_move = best_move;
+      best_move = if enemy_estimation < estimation { cur_best_move } else { None };
+      best_estimation = estimation;
+      reached_depth = depth;
+      let elapsed_time = (time::precise_time_ns() - start_time) / 1000000;
+      if min_time < max_time && elapsed_time >= min_time as u64 && best_move.is_some() && best_move == last_best_move {
+        info!(target: MINIMAX_STR, "Best move is stable after {} ms.", elapsed_time);
+        should_stop.store(true, Ordering::Relaxed);
         break;
       }
       depth += 1;
       trajectories_pruning = TrajectoriesPruning::inc_exists(field, player, depth, &mut empty_board, rng, &trajectories_pruning, &should_stop);
     }
-    best_move
+    info!(target: MINIMAX_STR, "Reached depth is {}, the best move is {:?}.", reached_depth, best_move.map(|pos| (field.to_x(pos), field.to_y(pos))));
+    (best_move, reached_depth)
   })
 }
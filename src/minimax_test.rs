@@ -0,0 +1,188 @@
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use rand::{Rng, XorShiftRng, SeedableRng};
+use time;
+use player::Player;
+use zobrist::Zobrist;
+use field;
+use field::{Pos, Field};
+use rules::Rules;
+use image;
+use config::MinimaxType;
+use hash_table::{HashTable, HashBound};
+use minimax;
+use minimax::MinimaxOptions;
+
+const HASH_TABLE_SIZE: usize = 1048576;
+
+fn construct_field(image: &str) -> Field {
+  let (width, height, moves) = image::parse(image).unwrap();
+  let mut rng = XorShiftRng::new_unseeded();
+  let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
+  let mut field = Field::new(width, height, zobrist, Rules::default());
+  for (x, y, player) in moves.into_iter() {
+    let pos = field.to_pos(x, y);
+    field.put_point(pos, player);
+  }
+  field
+}
+
+// Single-threaded search is deterministic, so its results can be compared exactly.
+fn options(minimax_type: MinimaxType) -> MinimaxOptions {
+  MinimaxOptions {
+    minimax_type: minimax_type,
+    threads_count: 1,
+    quiescence_depth: 4
+  }
+}
+
+fn estimation(field: &Field, player: Player, depth: u32, options: MinimaxOptions) -> (i32, Option<Pos>) {
+  let hash_table = HashTable::new(HASH_TABLE_SIZE);
+  let mut rng = XorShiftRng::new_unseeded();
+  let should_stop = AtomicBool::new(false);
+  minimax::minimax_estimation(&mut field.clone(), player, &hash_table, &mut rng, depth, options, &should_stop)
+}
+
+const CAPTURE_IMAGE: &'static str = "
+  ......
+  ..a...
+  .aB...
+  ..a...
+  ......
+  ";
+
+const THREATS_IMAGE: &'static str = "
+  ........
+  ..a..B..
+  .aB..aB.
+  ..a..B..
+  ........
+  ";
+
+#[test]
+fn mtdf_agrees_with_nega_scout() {
+  for image in &[CAPTURE_IMAGE, THREATS_IMAGE] {
+    let field = construct_field(image);
+    for &player in &[Player::Red, Player::Black] {
+      for depth in 1 .. 5 {
+        let (nega_scout_estimation, _) = estimation(&field, player, depth, options(MinimaxType::NegaScout));
+        let (mtdf_estimation, _) = estimation(&field, player, depth, options(MinimaxType::MTDF));
+        assert_eq!(nega_scout_estimation, mtdf_estimation);
+      }
+    }
+  }
+}
+
+#[test]
+fn hash_table_keeps_root_result() {
+  let field = construct_field(CAPTURE_IMAGE);
+  let capture_pos = field.to_pos(3, 2);
+  let hash_table = HashTable::new(HASH_TABLE_SIZE);
+  let mut rng = XorShiftRng::new_unseeded();
+  let should_stop = AtomicBool::new(false);
+  let result = minimax::minimax_estimation(&mut field.clone(), Player::Red, &hash_table, &mut rng, 3, options(MinimaxType::NegaScout), &should_stop);
+  assert_eq!(result, (1, Some(capture_pos)));
+  let entry = hash_table.get(field.hash(), Player::Red).unwrap();
+  assert_eq!(entry.depth, 3);
+  assert_eq!(entry.bound, HashBound::Exact);
+  assert_eq!(entry.value, 1);
+  assert_eq!(entry.best_move, Some(capture_pos));
+  let result = minimax::minimax_estimation(&mut field.clone(), Player::Red, &hash_table, &mut rng, 3, options(MinimaxType::NegaScout), &should_stop);
+  assert_eq!(result, (1, Some(capture_pos)));
+}
+
+fn random_field(width: u32, height: u32, moves_count: usize, seed: u32) -> Field {
+  let mut rng = XorShiftRng::from_seed([3, seed, 7, 11]);
+  let zobrist = Arc::new(Zobrist::new(field::length(width, height) * 2, &mut rng));
+  let mut field = Field::new(width, height, zobrist, Rules::default());
+  let mut moves = (field::min_pos(width) .. field::max_pos(width, height)).collect::<Vec<Pos>>();
+  rng.shuffle(&mut moves);
+  let mut player = Player::Red;
+  for pos in moves {
+    if field.moves_count() == moves_count {
+      break;
+    }
+    if field.is_putting_allowed(pos) {
+      field.put_point(pos, player);
+      player = player.next();
+    }
+  }
+  field
+}
+
+#[test]
+fn minimax_with_time_returns_reached_depth() {
+  let field = construct_field(CAPTURE_IMAGE);
+  let hash_table = HashTable::new(HASH_TABLE_SIZE);
+  let mut rng = XorShiftRng::new_unseeded();
+  let should_stop = AtomicBool::new(false);
+  let (best_move, depth) = minimax::minimax_with_time(&mut field.clone(), Player::Red, &hash_table, &mut rng, 500, options(MinimaxType::NegaScout), &should_stop);
+  assert_eq!(best_move, Some(field.to_pos(3, 2)));
+  assert!(depth >= 1);
+}
+
+const FAR_THREAT_IMAGE: &'static str = "
+  ............
+  ..a......B..
+  .aB.....BaB.
+  ..a.........
+  ............
+  ";
+
+#[test]
+fn quiescence_sees_surround_behind_horizon() {
+  let no_quiescence_options = MinimaxOptions { quiescence_depth: 0, .. options(MinimaxType::NegaScout) };
+  // Black's surrounding is next to red's capture in the first image and far from it in the second one.
+  for image in &[THREATS_IMAGE, FAR_THREAT_IMAGE] {
+    let field = construct_field(image);
+    // Without quiescence depth 1 stops right after red's capture and keeps its score.
+    assert_eq!(estimation(&field, Player::Red, 1, no_quiescence_options).0, 1);
+    // Quiescence finds that black surrounds in return, as the search one ply deeper does.
+    assert_eq!(estimation(&field, Player::Red, 1, options(MinimaxType::NegaScout)).0, 0);
+    assert_eq!(estimation(&field, Player::Red, 2, options(MinimaxType::NegaScout)).0, 0);
+  }
+}
+
+#[test]
+fn interrupted_iteration_keeps_proven_move() {
+  let field = construct_field(CAPTURE_IMAGE);
+  let capture_pos = field.to_pos(3, 2);
+  for &time in &[1, 5, 20, 100] {
+    let hash_table = HashTable::new(HASH_TABLE_SIZE);
+    let mut rng = XorShiftRng::new_unseeded();
+    let should_stop = AtomicBool::new(false);
+    let (best_move, _) = minimax::minimax_with_time(&mut field.clone(), Player::Red, &hash_table, &mut rng, time, options(MinimaxType::NegaScout), &should_stop);
+    assert!(best_move.is_none() || best_move == Some(capture_pos));
+  }
+}
+
+#[test]
+fn lazy_smp_finds_legal_bounded_move() {
+  let lazy_smp_options = MinimaxOptions { threads_count: 4, .. options(MinimaxType::NegaScout) };
+  for image in &[CAPTURE_IMAGE, THREATS_IMAGE] {
+    let field = construct_field(image);
+    for depth in 1 .. 5 {
+      // Helper threads fill the shared hash table in a nondeterministic order, so the result may differ from the single-threaded one.
+      let (lazy_smp_estimation, best_move) = estimation(&field, Player::Red, depth, lazy_smp_options);
+      assert!(lazy_smp_estimation.abs() <= field.moves_count() as i32);
+      if let Some(pos) = best_move {
+        assert!(field.is_putting_allowed(pos));
+      }
+    }
+  }
+}
+
+// Time to search a fixed set of positions with 1 - 16 threads, run with `cargo test --release -- --ignored --nocapture lazy_smp_scaling`.
+#[test]
+#[ignore]
+fn lazy_smp_scaling() {
+  let fields = (1 .. 6).map(|seed| random_field(20, 20, 40, seed)).collect::<Vec<Field>>();
+  for &threads_count in &[1, 2, 4, 8, 16] {
+    let lazy_smp_options = MinimaxOptions { threads_count: threads_count, .. options(MinimaxType::NegaScout) };
+    let start_time = time::precise_time_ns();
+    for field in &fields {
+      estimation(field, field.cur_player(), 6, lazy_smp_options);
+    }
+    println!("{} threads: {} ms.", threads_count, (time::precise_time_ns() - start_time) / 1000000);
+  }
+}
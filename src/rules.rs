@@ -0,0 +1,85 @@
+use std::str::FromStr;
+
+const NO_EMPTY_BASE_CAPTURE_STR: &'static str = "no_empty_base_capture";
+
+const MANUAL_SURROUND_STR: &'static str = "manual_surround";
+
+const KEEP_BASE_IN_BASE_STR: &'static str = "keep_base_in_base";
+
+const GROUNDING_STR: &'static str = "grounding";
+
+const TORUS_STR: &'static str = "torus";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SurroundRule {
+  // Every move captures all enemy points it surrounds.
+  Automatic,
+  // A move captures surrounded enemy points only if a surrounding is declared with it.
+  // Searches always declare surroundings, for their own moves as well as for the enemy's ones.
+  Manual
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BaseInBaseRule {
+  // Own points inside a captured enemy base are freed and returned to the player.
+  Free,
+  // Own points inside a captured enemy base stay captured.
+  Keep
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Topology {
+  // A rectangle bounded by its edges.
+  Plane,
+  // Opposite edges of the rectangle are glued together, so the board wraps around. Both sides must be at least 3 cells long.
+  Torus
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Rules {
+  // Whether surrounded empty territory becomes an empty base, so that an enemy move into it is captured immediately.
+  pub empty_base_capture: bool,
+  pub surround: SurroundRule,
+  pub base_in_base: BaseInBaseRule,
+  // Whether points that are not connected to the board edge are lost at the end of the game.
+  pub grounding: bool,
+  pub topology: Topology
+}
+
+impl Default for Rules {
+  fn default() -> Rules {
+    Rules {
+      empty_base_capture: true,
+      surround: SurroundRule::Automatic,
+      base_in_base: BaseInBaseRule::Free,
+      grounding: false,
+      topology: Topology::Plane
+    }
+  }
+}
+
+impl Rules {
+  // A torus needs at least 3 cells on each side, so that neighbours of a cell are different cells.
+  pub fn supports_size(&self, width: u32, height: u32) -> bool {
+    self.topology != Topology::Torus || width >= 3 && height >= 3
+  }
+}
+
+// Parses a comma separated list of differences from the default rules, e.g. "grounding,torus".
+impl FromStr for Rules {
+  type Err = &'static str;
+  fn from_str(s: &str) -> Result<Rules, &'static str> {
+    let mut rules = Rules::default();
+    for rule in s.split(',') {
+      match rule {
+        NO_EMPTY_BASE_CAPTURE_STR => rules.empty_base_capture = false,
+        MANUAL_SURROUND_STR => rules.surround = SurroundRule::Manual,
+        KEEP_BASE_IN_BASE_STR => rules.base_in_base = BaseInBaseRule::Keep,
+        GROUNDING_STR => rules.grounding = true,
+        TORUS_STR => rules.topology = Topology::Torus,
+        _ => return Err("Invalid rule!")
+      }
+    }
+    Ok(rules)
+  }
+}
@@ -0,0 +1,172 @@
+use std::char;
+use std::iter::Peekable;
+use player::Player;
+use field;
+use field::Field;
+
+const SGF_STR: &'static str = "sgf";
+
+const POINTS_GAME_TYPE: u32 = 40;
+
+const MAX_SIZE: u32 = 52;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct SgfGame {
+  pub width: u32,
+  pub height: u32,
+  pub moves: Vec<(u32, u32, Player)>
+}
+
+fn coordinate_from_char(c: char) -> Option<u32> {
+  match c {
+    'a' ... 'z' => Some(c as u32 - 'a' as u32),
+    'A' ... 'Z' => Some(c as u32 - 'A' as u32 + 26),
+    _ => None
+  }
+}
+
+fn coordinate_to_char(coordinate: u32) -> char {
+  if coordinate < 26 {
+    char::from_u32('a' as u32 + coordinate).unwrap()
+  } else {
+    char::from_u32('A' as u32 + coordinate - 26).unwrap()
+  }
+}
+
+fn parse_size(value: &str) -> Result<(u32, u32), &'static str> {
+  let mut split = value.split(':');
+  let width = try!(split.next().and_then(|width_str| width_str.trim().parse::<u32>().ok()).ok_or("Invalid board size!"));
+  let height = match split.next() {
+    Some(height_str) => try!(height_str.trim().parse::<u32>().map_err(|_| "Invalid board size!")),
+    None => width
+  };
+  if split.next().is_some() || width == 0 || height == 0 || width > MAX_SIZE || height > MAX_SIZE {
+    Err("Invalid board size!")
+  } else {
+    Ok((width, height))
+  }
+}
+
+fn parse_move(value: &str, player: Player) -> Result<(u32, u32, Player), &'static str> {
+  let mut chars = value.chars();
+  match (chars.next().and_then(coordinate_from_char), chars.next().and_then(coordinate_from_char), chars.next()) {
+    (Some(x), Some(y), None) => Ok((x, y, player)),
+    _ => Err("Invalid move!")
+  }
+}
+
+// Reads property values of the form [value][value]... starting at the current position.
+fn parse_values<I: Iterator<Item = char>>(chars: &mut Peekable<I>) -> Result<Vec<String>, &'static str> {
+  let mut values = Vec::new();
+  loop {
+    while chars.peek().map_or(false, |c| c.is_whitespace()) {
+      chars.next();
+    }
+    if chars.peek() != Some(&'[') {
+      return Ok(values);
+    }
+    chars.next();
+    let mut value = String::new();
+    loop {
+      match chars.next() {
+        Some('\\') => {
+          if let Some(c) = chars.next() {
+            value.push(c);
+          }
+        },
+        Some(']') => break,
+        Some(c) => value.push(c),
+        None => return Err("Unexpected end of property value!")
+      }
+    }
+    values.push(value);
+  }
+}
+
+// Parses the main line of the first game tree. Setup properties (AB, AW) and moves (B, W) are returned as a single moves sequence.
+pub fn parse(s: &str) -> Result<SgfGame, &'static str> {
+  let mut chars = s.chars().peekable();
+  let mut size = None;
+  let mut moves = Vec::new();
+  while chars.peek().map_or(false, |&c| c != '(') {
+    chars.next();
+  }
+  if chars.next() != Some('(') {
+    return Err("Game tree is not found!");
+  }
+  let mut ident = String::new();
+  loop {
+    match chars.peek().cloned() {
+      Some(')') | None => break,
+      Some(c) if c.is_alphabetic() => {
+        ident.push(c);
+        chars.next();
+      },
+      Some('[') => {
+        let values = try!(parse_values(&mut chars));
+        match ident.as_str() {
+          "GM" => {
+            if values.first().and_then(|value| value.trim().parse::<u32>().ok()) != Some(POINTS_GAME_TYPE) {
+              return Err("Not a points game!");
+            }
+          },
+          "SZ" => size = Some(try!(values.first().ok_or("Invalid board size!").and_then(|value| parse_size(value.as_str())))),
+          "AB" | "B" => {
+            for value in &values {
+              moves.push(try!(parse_move(value.as_str(), Player::Black)));
+            }
+          },
+          "AW" | "W" => {
+            for value in &values {
+              moves.push(try!(parse_move(value.as_str(), Player::Red)));
+            }
+          },
+          _ => { }
+        }
+        ident.clear();
+      },
+      Some(_) => {
+        ident.clear();
+        chars.next();
+      }
+    }
+  }
+  let (width, height) = try!(size.ok_or("Board size is not specified!"));
+  if moves.iter().any(|&(x, y, _)| x >= width || y >= height) {
+    return Err("Move is out of the board!");
+  }
+  debug!(target: SGF_STR, "Parsed game with width {0}, height {1} and {2} moves.", width, height, moves.len());
+  Ok(SgfGame {
+    width: width,
+    height: height,
+    moves: moves
+  })
+}
+
+// Passes are not kept since moves are stored with explicit players.
+pub fn from_field(field: &Field) -> SgfGame {
+  SgfGame {
+    width: field.width(),
+    height: field.height(),
+    moves: field.points_seq().iter().filter(|&&pos| !field::is_pass(pos)).map(|&pos| (field.to_x(pos), field.to_y(pos), field.get_player(pos))).collect()
+  }
+}
+
+pub fn write(game: &SgfGame) -> String {
+  let mut result = format!("(;GM[{0}]FF[4]CA[UTF-8]AP[opai-rs:{1}]", POINTS_GAME_TYPE, env!("CARGO_PKG_VERSION"));
+  if game.width == game.height {
+    result.push_str(format!("SZ[{0}]", game.width).as_str());
+  } else {
+    result.push_str(format!("SZ[{0}:{1}]", game.width, game.height).as_str());
+  }
+  for &(x, y, player) in &game.moves {
+    result.push(';');
+    result.push(if player == Player::Black { 'B' } else { 'W' });
+    result.push('[');
+    result.push(coordinate_to_char(x));
+    result.push(coordinate_to_char(y));
+    result.push(']');
+  }
+  result.push_str(")\n");
+  result
+}
@@ -0,0 +1,43 @@
+use player::Player;
+use sgf;
+use sgf::SgfGame;
+
+#[test]
+fn parse_moves() {
+  let game = sgf::parse("(;GM[40]FF[4]SZ[39:32];B[ab];W[cd];B[Aa])").unwrap();
+  assert_eq!(game.width, 39);
+  assert_eq!(game.height, 32);
+  assert_eq!(game.moves, vec![(0, 1, Player::Black), (2, 3, Player::Red), (26, 0, Player::Black)]);
+}
+
+#[test]
+fn parse_setup_and_square_size() {
+  let game = sgf::parse("(;GM[40]SZ[20]AB[aa][bb]AW[cc];W[dd])").unwrap();
+  assert_eq!(game.width, 20);
+  assert_eq!(game.height, 20);
+  assert_eq!(game.moves, vec![(0, 0, Player::Black), (1, 1, Player::Black), (2, 2, Player::Red), (3, 3, Player::Red)]);
+}
+
+#[test]
+fn parse_main_line_only() {
+  let game = sgf::parse("(;GM[40]SZ[10]C[comment \\] with bracket];B[aa](;W[bb];B[cc])(;W[dd]))").unwrap();
+  assert_eq!(game.moves, vec![(0, 0, Player::Black), (1, 1, Player::Red), (2, 2, Player::Black)]);
+}
+
+#[test]
+fn parse_errors() {
+  assert!(sgf::parse("(;GM[1]SZ[19];B[aa])").is_err());
+  assert!(sgf::parse("(;GM[40];B[aa])").is_err());
+  assert!(sgf::parse("(;GM[40]SZ[5];B[ff])").is_err());
+  assert!(sgf::parse("(;GM[40]SZ[5];B[a").is_err());
+}
+
+#[test]
+fn write_parse_round_trip() {
+  let game = SgfGame {
+    width: 39,
+    height: 32,
+    moves: vec![(0, 0, Player::Red), (38, 31, Player::Black), (26, 5, Player::Red)]
+  };
+  assert_eq!(sgf::parse(sgf::write(&game).as_str()), Ok(game));
+}
@@ -0,0 +1,76 @@
+// Little-endian encoding of primitive values for binary snapshots.
+
+pub fn write_u8(bytes: &mut Vec<u8>, value: u8) {
+  bytes.push(value);
+}
+
+pub fn write_u32(bytes: &mut Vec<u8>, value: u32) {
+  for i in 0 .. 4 {
+    bytes.push((value >> (i * 8)) as u8);
+  }
+}
+
+pub fn write_u64(bytes: &mut Vec<u8>, value: u64) {
+  for i in 0 .. 8 {
+    bytes.push((value >> (i * 8)) as u8);
+  }
+}
+
+pub fn write_i32(bytes: &mut Vec<u8>, value: i32) {
+  write_u32(bytes, value as u32);
+}
+
+pub struct SnapshotReader<'a> {
+  bytes: &'a [u8],
+  index: usize
+}
+
+impl<'a> SnapshotReader<'a> {
+  pub fn new(bytes: &'a [u8]) -> SnapshotReader<'a> {
+    SnapshotReader {
+      bytes: bytes,
+      index: 0
+    }
+  }
+
+  pub fn is_finished(&self) -> bool {
+    self.index == self.bytes.len()
+  }
+
+  pub fn read_u8(&mut self) -> Result<u8, &'static str> {
+    if self.index < self.bytes.len() {
+      self.index += 1;
+      Ok(self.bytes[self.index - 1])
+    } else {
+      Err("Unexpected end of snapshot!")
+    }
+  }
+
+  pub fn read_u32(&mut self) -> Result<u32, &'static str> {
+    let mut value = 0u32;
+    for i in 0 .. 4 {
+      value |= (try!(self.read_u8()) as u32) << (i * 8);
+    }
+    Ok(value)
+  }
+
+  pub fn read_u64(&mut self) -> Result<u64, &'static str> {
+    let mut value = 0u64;
+    for i in 0 .. 8 {
+      value |= (try!(self.read_u8()) as u64) << (i * 8);
+    }
+    Ok(value)
+  }
+
+  pub fn read_i32(&mut self) -> Result<i32, &'static str> {
+    self.read_u32().map(|value| value as i32)
+  }
+
+  pub fn read_bool(&mut self) -> Result<bool, &'static str> {
+    match try!(self.read_u8()) {
+      0 => Ok(false),
+      1 => Ok(true),
+      _ => Err("Invalid snapshot!")
+    }
+  }
+}
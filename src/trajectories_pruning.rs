@@ -1,3 +1,4 @@
+use std::cmp;
 use std::ops::Index;
 use std::sync::atomic::{AtomicBool, Ordering};
 use rand::Rng;
@@ -5,6 +6,9 @@ use zobrist::Zobrist;
 use player::Player;
 use field::{Pos, Field};
 
+// Distance from the last move within which quiescence search looks for new surroundings.
+const QUIESCENCE_RADIUS: u32 = 2;
+
 struct Trajectory {
   points: Vec<Pos>,
   hash: u64,
@@ -99,6 +103,30 @@ impl TrajectoriesPruning {
     }
   }
 
+  fn add_capture(field: &mut Field, trajectories: &mut Vec<Trajectory>, player: Player, pos: Pos) {
+    if field.is_putting_allowed(pos) && field.has_near_points(pos, player) && !field.is_players_empty_base(pos, player) {
+      field.put_point(pos, player);
+      if field.get_delta_score(player) > 0 {
+        TrajectoriesPruning::add_trajectory(field, trajectories, &[pos], player);
+      }
+      field.undo();
+    }
+  }
+
+  fn build_near_trajectories(field: &mut Field, trajectories: &mut Vec<Trajectory>, player: Player, last_pos: Pos, should_stop: &AtomicBool) {
+    let x = field.to_x(last_pos);
+    let y = field.to_y(last_pos);
+    for cur_y in y.saturating_sub(QUIESCENCE_RADIUS) .. cmp::min(y + QUIESCENCE_RADIUS + 1, field.height()) {
+      for cur_x in x.saturating_sub(QUIESCENCE_RADIUS) .. cmp::min(x + QUIESCENCE_RADIUS + 1, field.width()) {
+        if should_stop.load(Ordering::Relaxed) {
+          return;
+        }
+        let pos = field.to_pos(cur_x, cur_y);
+        TrajectoriesPruning::add_capture(field, trajectories, player, pos);
+      }
+    }
+  }
+
   fn intersection_hash(trajectory1: &Trajectory, trajectory2: &Trajectory, zobrist: &Zobrist, empty_board: &mut Vec<u32>) -> u64 {
     let mut result = trajectory1.hash() ^ trajectory2.hash();
     for &pos in trajectory1.points() {
@@ -254,6 +282,31 @@ impl TrajectoriesPruning {
     }
   }
 
+  // Moves that surround in one move for either player. Surroundings of the parent position that are still possible are reused,
+  // and new ones are searched only around the last move, so a quiescence node doesn't scan the whole field.
+  pub fn quiescence<T: Rng>(field: &mut Field, player: Player, empty_board: &mut Vec<u32>, rng: &mut T, last: &TrajectoriesPruning, last_pos: Pos, should_stop: &AtomicBool) -> TrajectoriesPruning {
+    let mut cur_trajectories = Vec::new();
+    let mut enemy_trajectories = Vec::new();
+    for trajectory in last.enemy_trajectories.iter().filter(|trajectory| trajectory.len() == 1) {
+      TrajectoriesPruning::add_capture(field, &mut cur_trajectories, player, trajectory.points()[0]);
+    }
+    for trajectory in last.cur_trajectories.iter().filter(|trajectory| trajectory.len() == 1) {
+      TrajectoriesPruning::add_capture(field, &mut enemy_trajectories, player.next(), trajectory.points()[0]);
+    }
+    TrajectoriesPruning::build_near_trajectories(field, &mut cur_trajectories, player, last_pos, should_stop);
+    TrajectoriesPruning::build_near_trajectories(field, &mut enemy_trajectories, player.next(), last_pos, should_stop);
+    if should_stop.load(Ordering::Relaxed) {
+      return TrajectoriesPruning::empty();
+    }
+    TrajectoriesPruning::exclude_trajectories(&mut cur_trajectories, &mut enemy_trajectories, field.zobrist(), empty_board);
+    let moves = TrajectoriesPruning::calculate_moves(&cur_trajectories, &enemy_trajectories, empty_board, rng);
+    TrajectoriesPruning {
+      cur_trajectories: cur_trajectories,
+      enemy_trajectories: enemy_trajectories,
+      moves: moves
+    }
+  }
+
   pub fn dec_and_swap_exists<T: Rng>(field: &Field, depth: u32, empty_board: &mut Vec<u32>, rng: &mut T, exists: &TrajectoriesPruning, should_stop: &AtomicBool) -> TrajectoriesPruning {
     if depth == 0 {
       return TrajectoriesPruning::empty();
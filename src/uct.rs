@@ -1,16 +1,21 @@
-use std::{ptr, thread, mem};
+use std::{ptr, mem, iter};
+use std::sync::Mutex;
 use std::sync::atomic::{AtomicBool, AtomicIsize, AtomicUsize, AtomicPtr, Ordering};
 use rand::{Rng, XorShiftRng};
 use crossbeam;
+use time;
 use config;
 use config::{UcbType, UctKomiType};
 use player::Player;
+use field;
 use field::{Pos, Field};
 use wave_pruning::WavePruning;
 use common;
 
 const UCT_STR: &'static str = "uct";
 
+const STABILITY_CHECK_INTERVAL: u32 = 100;
+
 #[unsafe_no_drop_flag]
 struct UctNode {
   wins: AtomicUsize,
@@ -152,6 +157,71 @@ impl UctNode {
   }
 }
 
+pub struct UctMoveStats {
+  pub pos: Pos,
+  pub wins: usize,
+  pub draws: usize,
+  pub visits: usize,
+  pub win_rate: f64,
+  pub pv: Vec<Pos>
+}
+
+pub struct UctScoreEstimation {
+  pub playouts: usize,
+  pub score_lead: f64,
+  pub ownership: Vec<f64>
+}
+
+struct PlayoutsEstimation {
+  playouts: usize,
+  score_sum: i64,
+  ownership: Vec<i64>
+}
+
+impl PlayoutsEstimation {
+  fn new(length: Pos) -> PlayoutsEstimation {
+    PlayoutsEstimation {
+      playouts: 0,
+      score_sum: 0,
+      ownership: iter::repeat(0).take(length).collect()
+    }
+  }
+
+  fn add_playout(&mut self, field: &Field, player: Player) {
+    self.playouts += 1;
+    self.score_sum += field.final_score(player) as i64;
+    // Points lost at the end of the game belong to the enemy, as in the final score.
+    let mut lost = iter::repeat(false).take(field.length()).collect::<Vec<bool>>();
+    for &pos in field.final_lost_points(Player::Red).iter().chain(field.final_lost_points(Player::Black).iter()) {
+      lost[pos] = true;
+    }
+    for pos in field.min_pos() .. field.max_pos() + 1 {
+      match field.get_owner(pos).map(|owner| if lost[pos] { owner.next() } else { owner }) {
+        Some(owner) if owner == player => self.ownership[pos] += 1,
+        Some(_) => self.ownership[pos] -= 1,
+        None => { }
+      }
+    }
+  }
+
+  fn merge(&mut self, other: &PlayoutsEstimation) {
+    self.playouts += other.playouts;
+    self.score_sum += other.score_sum;
+    for (ownership, &other_ownership) in self.ownership.iter_mut().zip(other.ownership.iter()) {
+      *ownership += other_ownership;
+    }
+  }
+
+  fn to_score_estimation(&self) -> UctScoreEstimation {
+    let playouts = if self.playouts > 0 { self.playouts as f64 } else { 1f64 };
+    UctScoreEstimation {
+      playouts: self.playouts,
+      score_lead: self.score_sum as f64 / playouts,
+      ownership: self.ownership.iter().map(|&ownership| ownership as f64 / playouts).collect()
+    }
+  }
+}
+
 pub struct UctRoot {
   node: Option<Box<UctNode>>,
   player: Player,
@@ -242,12 +312,12 @@ impl UctRoot {
           break;
         }
         let next_pos = points_seq[self.moves_count];
-        debug!(target: UCT_STR, "Next move is ({0}, {1}), player {2}.", field.to_x(next_pos), field.to_y(next_pos), self.player);
-        if !field.is_players_point(next_pos, self.player) {
+        if field::is_pass(next_pos) || !field.is_players_point(next_pos, self.player) {
           self.clear();
           self.init(field, player);
           break;
         }
+        debug!(target: UCT_STR, "Next move is ({0}, {1}), player {2}.", field.to_x(next_pos), field.to_y(next_pos), self.player);
         let mut next = self.node.as_ref().unwrap().get_child();
         while next.is_some() && next.as_ref().unwrap().pos != next_pos {
           next = next.unwrap().get_sibling();
@@ -288,7 +358,7 @@ impl UctRoot {
 
   fn random_result(field: &Field, player: Player, komi: i32) -> Option<Player> {
     let red_komi = if player == Player::Red { komi } else { -komi };
-    let red_score = field.score(Player::Red);
+    let red_score = field.final_score(Player::Red);
     if red_score > red_komi {
       Some(Player::Red)
     } else if red_score < red_komi {
@@ -298,12 +368,33 @@ impl UctRoot {
     }
   }
 
+  // Whether the player wins the game by grounding right now.
+  fn is_grounding_won(field: &Field, player: Player, cur_player: Player, komi: i32) -> bool {
+    let cur_komi = if cur_player == player { komi } else { -komi };
+    let score = field.score(cur_player);
+    score > cur_komi && score - field.ungrounded_points(cur_player).len() as i32 > cur_komi
+  }
+
+  // With the grounding rule a player ends the playout by grounding when it wins the game. It's checked only
+  // for two moves after a capture, since grounding without a capture can't be better than grounding a move earlier.
   fn play_random_game<T: Rng>(field: &mut Field, player: Player, rng: &mut T, possible_moves: &mut Vec<Pos>, komi: i32) -> Option<Player> {
     rng.shuffle(possible_moves);
+    let grounding = field.rules().grounding && field.has_edge();
+    let mut grounding_checks = if grounding { 2 } else { 0 };
     let mut cur_player = player;
     for &pos in possible_moves.iter() {
       if field.is_putting_allowed(pos) && !field.is_empty_base(pos) {
+        if grounding_checks > 0 {
+          grounding_checks -= 1;
+          if UctRoot::is_grounding_won(field, player, cur_player, komi) {
+            field.ground(cur_player);
+            break;
+          }
+        }
         field.put_point(pos, cur_player);
+        if grounding && field.get_delta_score(cur_player) != 0 {
+          grounding_checks = 2;
+        }
         cur_player = cur_player.next();
       }
     }
@@ -370,7 +461,9 @@ impl UctRoot {
   }
 
   fn play_simulation_rec<T: Rng>(field: &mut Field, player: Player, node: &UctNode, possible_moves: &mut Vec<Pos>, rng: &mut T, komi: i32, depth: u32) -> Option<Player> {
-    let random_result = if node.get_visits() < config::uct_when_create_children() || depth == config::uct_depth() {
+    let random_result = if field.is_game_over() {
+      UctRoot::random_result(field, player, komi)
+    } else if node.get_visits() < config::uct_when_create_children() || depth == config::uct_depth() {
       UctRoot::play_random_game(field, player, rng, possible_moves, komi)
     } else {
       if node.get_child_ref().is_none() {
@@ -444,9 +537,8 @@ impl UctRoot {
     }
   }
 
-  fn best_move_generic<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, should_stop: &AtomicBool, max_iterations_count: usize) -> Option<Pos> {
-    info!(target: UCT_STR, "Generating best move for player {0}.", player);
-    debug!(target: UCT_STR, "Moves history: {:?}.", field.points_seq().iter().map(|&pos| (field.to_x(pos), field.to_y(pos), field.get_player(pos))).collect::<Vec<(u32, u32, Player)>>());
+  fn search<T: Rng, F: FnMut(&UctRoot)>(&mut self, field: &Field, player: Player, rng: &mut T, should_stop: &AtomicBool, max_iterations_count: usize, estimation: Option<&Mutex<PlayoutsEstimation>>, report_interval: u32, mut report: F) {
+    debug!(target: UCT_STR, "Moves history: {:?}.", field.points_seq().iter().filter(|&&pos| !field::is_pass(pos)).map(|&pos| (field.to_x(pos), field.to_y(pos), field.get_player(pos))).collect::<Vec<(u32, u32, Player)>>());
     debug!(target: UCT_STR, "Next random u64: {0}.", rng.gen::<u64>());
     self.update(field, player, rng);
     info!(target: UCT_STR, "Komi is {0}, type is {1}.", self.komi.load(Ordering::Relaxed), config::uct_komi_type());
@@ -460,17 +552,92 @@ impl UctRoot {
           let mut local_field = field.clone();
           let mut local_rng = xor_shift_rng;
           let mut possible_moves = self.wave_pruning.moves().clone();
+          let mut local_estimation = estimation.map(|_| PlayoutsEstimation::new(field.length()));
           while !should_stop.load(Ordering::Relaxed) && iterations.load(Ordering::Relaxed) < max_iterations_count {
             self.play_simulation(&mut local_field, player, &mut possible_moves, &mut local_rng, &ratched);
+            if let Some(ref mut local_estimation) = local_estimation {
+              local_estimation.add_playout(&local_field, player);
+            }
+            // A playout may end by grounding, which is undone separately from moves.
+            if local_field.grounded_player().is_some() {
+              local_field.undo();
+            }
             for _ in 0 .. local_field.moves_count() - self.moves_count {
               local_field.undo();
             }
             iterations.fetch_add(1, Ordering::Relaxed);
           }
+          if let (Some(estimation), Some(local_estimation)) = (estimation, local_estimation) {
+            estimation.lock().unwrap().merge(&local_estimation);
+          }
         });
       }
+      // The search is reported only while it runs, so the control thread never delays the end of the search.
+      if report_interval > 0 {
+        while iterations.load(Ordering::Relaxed) < max_iterations_count && common::sleep_unless_stopped(report_interval, should_stop) {
+          report(&*self);
+        }
+      }
     });
     info!(target: UCT_STR, "Iterations count: {0}.", iterations.load(Ordering::Relaxed));
+  }
+
+  fn principal_variation(node: &UctNode, max_length: u32) -> Vec<Pos> {
+    let mut result = vec![node.get_pos()];
+    let mut cur = node;
+    while (result.len() as u32) < max_length {
+      let mut best = None;
+      let mut best_visits = 0;
+      let mut next = cur.get_child_ref();
+      while let Some(next_node) = next {
+        let visits = next_node.get_visits();
+        if visits != usize::max_value() && visits > best_visits {
+          best_visits = visits;
+          best = Some(next_node);
+        }
+        next = next_node.get_sibling_ref();
+      }
+      if let Some(best_node) = best {
+        result.push(best_node.get_pos());
+        cur = best_node;
+      } else {
+        break;
+      }
+    }
+    result
+  }
+
+  pub fn stats(&self, moves_count: usize) -> Vec<UctMoveStats> {
+    let mut result = Vec::new();
+    if let Some(ref root) = self.node {
+      let mut next = root.get_child_ref();
+      while let Some(next_node) = next {
+        let visits = next_node.get_visits();
+        if visits != 0 && visits != usize::max_value() {
+          result.push(UctMoveStats {
+            pos: next_node.get_pos(),
+            wins: next_node.get_wins(),
+            draws: next_node.get_draws(),
+            visits: visits,
+            win_rate: UctRoot::ucb(root, next_node, UcbType::Winrate),
+            pv: UctRoot::principal_variation(next_node, config::uct_depth() + 1)
+          });
+        }
+        next = next_node.get_sibling_ref();
+      }
+    }
+    result.sort_by(|stats1, stats2| stats2.visits.cmp(&stats1.visits));
+    result.truncate(moves_count);
+    result
+  }
+
+  fn best_move_generic<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, should_stop: &AtomicBool, max_iterations_count: usize) -> Option<Pos> {
+    info!(target: UCT_STR, "Generating best move for player {0}.", player);
+    self.search(field, player, rng, should_stop, max_iterations_count, None, 0, |_| { });
+    self.best_child(field, rng)
+  }
+
+  fn best_child<T: Rng>(&self, field: &Field, rng: &mut T) -> Option<Pos> {
     let mut best_uct = 0f64;
     let mut result = None;
     if let Some(ref root) = self.node {
@@ -492,19 +659,66 @@ impl UctRoot {
     result
   }
 
-  pub fn best_move_with_time<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, time: u32) -> Option<Pos> {
-    let should_stop = AtomicBool::new(false);
+  pub fn analyze<T: Rng, F: FnMut(&[UctMoveStats])>(&mut self, field: &Field, player: Player, rng: &mut T, should_stop: &AtomicBool, interval: u32, moves_count: usize, mut report: F) {
+    info!(target: UCT_STR, "Analyzing position for player {0}.", player);
+    self.search(field, player, rng, should_stop, usize::max_value(), None, interval, |uct| report(&uct.stats(moves_count)));
+    // Intermediate reports are skipped once the search is stopped, so the final statistics are reported exactly once.
+    report(&self.stats(moves_count));
+  }
+
+  pub fn estimate<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, time: u32, should_stop: &AtomicBool) -> UctScoreEstimation {
+    info!(target: UCT_STR, "Estimating score for player {0}.", player);
+    let estimation = Mutex::new(PlayoutsEstimation::new(field.length()));
+    let time_should_stop = AtomicBool::new(false);
     crossbeam::scope(|scope| {
-      scope.spawn(|| {
-        thread::sleep_ms(time);
-        should_stop.store(true, Ordering::Relaxed);
-      });
-      self.best_move_generic(field, player, rng, &should_stop, usize::max_value())
+      scope.spawn(|| common::wait_for_stop(time, should_stop, &time_should_stop));
+      self.search(field, player, rng, &time_should_stop, usize::max_value(), Some(&estimation), 0, |_| { });
+    });
+    let result = estimation.lock().unwrap().to_score_estimation();
+    info!(target: UCT_STR, "Score lead is {0} after {1} playouts.", result.score_lead, result.playouts);
+    result
+  }
+
+  pub fn ponder<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, should_stop: &AtomicBool) {
+    info!(target: UCT_STR, "Pondering for player {0}.", player);
+    self.search(field, player, rng, should_stop, usize::max_value(), None, 0, |_| { });
+  }
+
+  pub fn best_move_with_time<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, time: u32, should_stop: &AtomicBool) -> Option<Pos> {
+    let time_should_stop = AtomicBool::new(false);
+    crossbeam::scope(|scope| {
+      scope.spawn(|| common::wait_for_stop(time, should_stop, &time_should_stop));
+      self.best_move_generic(field, player, rng, &time_should_stop, usize::max_value())
     })
   }
 
-  pub fn best_move_with_iterations_count<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, iterations: usize) -> Option<Pos> {
-    let should_stop = AtomicBool::new(false);
-    self.best_move_generic(field, player, rng, &should_stop, iterations)
+  pub fn best_move_with_stability<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, min_time: u32, max_time: u32, should_stop: &AtomicBool) -> Option<Pos> {
+    info!(target: UCT_STR, "Generating best move for player {0} within {1} - {2} ms.", player, min_time, max_time);
+    let time_should_stop = AtomicBool::new(false);
+    let start_time = time::precise_time_ns();
+    let mut last_best_pos = None;
+    crossbeam::scope(|scope| {
+      scope.spawn(|| common::wait_for_stop(max_time, should_stop, &time_should_stop));
+      self.search(field, player, rng, &time_should_stop, usize::max_value(), None, STABILITY_CHECK_INTERVAL, |uct| {
+        let elapsed_time = (time::precise_time_ns() - start_time) / 1000000;
+        let best_pos = uct.stats(1).first().map(|move_stats| move_stats.pos);
+        if elapsed_time >= min_time as u64 && best_pos.is_some() && best_pos == last_best_pos {
+          info!(target: UCT_STR, "Best move is stable after {0} ms.", elapsed_time);
+          time_should_stop.store(true, Ordering::Relaxed);
+        }
+        last_best_pos = best_pos;
+      });
+    });
+    self.best_child(field, rng)
+  }
+
+  pub fn best_moves_with_iterations_count<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, iterations: usize, moves_count: usize, should_stop: &AtomicBool) -> Vec<UctMoveStats> {
+    info!(target: UCT_STR, "Generating {0} best moves for player {1}.", moves_count, player);
+    self.search(field, player, rng, should_stop, iterations, None, 0, |_| { });
+    self.stats(moves_count)
+  }
+
+  pub fn best_move_with_iterations_count<T: Rng>(&mut self, field: &Field, player: Player, rng: &mut T, iterations: usize, should_stop: &AtomicBool) -> Option<Pos> {
+    self.best_move_generic(field, player, rng, should_stop, iterations)
   }
 }
@@ -28,12 +28,14 @@ impl WavePruning {
 
   pub fn init(&mut self, field: &Field, radius: u32) {
     let width = field.width();
-    for &start_pos in field.points_seq() {
-      field::wave(width, start_pos, |pos| {
+    let height = field.height();
+    let topology = field.topology();
+    for &start_pos in field.points_seq().iter().filter(|&&pos| !field::is_pass(pos)) {
+      field::wave(width, height, topology, start_pos, |pos| {
         if pos == start_pos && self.moves_field[pos] == 0 {
           self.moves_field[pos] = 1;
           true
-        } else if self.moves_field[pos] != start_pos && field.is_putting_allowed(pos) && field::manhattan(width, start_pos, pos) <= radius {
+        } else if self.moves_field[pos] != start_pos && field.is_putting_allowed(pos) && field.manhattan(start_pos, pos) <= radius {
           if self.moves_field[pos] == 0 {
             self.moves.push(pos);
           }
@@ -60,14 +62,19 @@ impl WavePruning {
     });
     let points_seq = field.points_seq();
     let width = field.width();
+    let height = field.height();
+    let topology = field.topology();
     let mut added_moves = Vec::new();
     for i in last_moves_count .. field.moves_count() {
       let next_pos = points_seq[i];
-      field::wave(width, next_pos, |pos| {
+      if field::is_pass(next_pos) {
+        continue;
+      }
+      field::wave(width, height, topology, next_pos, |pos| {
         if pos == next_pos && moves_field[pos] == 0 {
           moves_field[pos] = 1;
           true
-        } else if moves_field[pos] != next_pos && field.is_putting_allowed(pos) && field::manhattan(width, next_pos, pos) <= radius {
+        } else if moves_field[pos] != next_pos && field.is_putting_allowed(pos) && field.manhattan(next_pos, pos) <= radius {
           if moves_field[pos] == 0 && pos != next_pos {
             moves.push(pos);
             added_moves.push(pos);